@@ -0,0 +1,76 @@
+use std::path::PathBuf;
+use std::process;
+
+use asm::link::Linker;
+use asm::AsmError;
+use clap::{Parser, ValueHint};
+use env_logger as logger;
+use log::error;
+
+fn main() {
+    // Initialize logger
+    logger::Builder::new()
+        .default_format()
+        .format_indent(Some(12))
+        .format_timestamp(None)
+        .parse_default_env()
+        .init();
+    // Parse opts
+    let args = Args::parse();
+
+    // Instantiate a linker
+    let mut l = Linker::new();
+    // Load each input object
+    for file in &args.objs {
+        l.load(file).unwrap_or_else(|err| {
+            eprintln!("{}: `{}`", AsmError::from(err), file.display());
+            process::exit(1);
+        });
+    }
+    // Produce a linked image
+    l.link().unwrap_or_else(|err| {
+        eprintln!("{}", AsmError::from(Box::from(err)));
+        process::exit(1);
+    });
+    // Write output file
+    l.out(&args.out).unwrap_or_else(|err| {
+        error!("{}: `{}`", err, &args.out.display());
+        process::exit(1);
+    });
+    // Write symbol map
+    if let Some(map) = &args.map {
+        l.map(map).unwrap_or_else(|err| {
+            error!("{}: `{}`", err, map.display());
+            process::exit(1);
+        });
+    }
+}
+
+/// Linker for the KAP-16 processor.
+#[derive(Debug, Parser)]
+#[clap(author, version, about)]
+struct Args {
+    /// Input object file
+    #[clap(parse(from_os_str))]
+    #[clap(min_values = 1)]
+    #[clap(value_hint = ValueHint::FilePath)]
+    objs: Vec<PathBuf>,
+
+    /// Output binary file
+    #[clap(short, long)]
+    #[clap(parse(from_os_str))]
+    #[clap(default_value = "a.out")]
+    #[clap(value_hint = ValueHint::FilePath)]
+    out: PathBuf,
+
+    /// Output a symbol map for debugging
+    #[clap(short, long)]
+    #[clap(parse(from_os_str))]
+    #[clap(value_hint = ValueHint::FilePath)]
+    map: Option<PathBuf>,
+
+    /// Use verbose output (-v, -vv, -vvv, etc.)
+    #[clap(short, long)]
+    #[clap(parse(from_occurrences))]
+    verbose: u8,
+}
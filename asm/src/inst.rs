@@ -1,7 +1,9 @@
 use std::error::Error;
 use std::fmt;
-use std::fmt::{Debug, Display};
-use std::str::FromStr;
+use std::fmt::Display;
+
+use isa::inst::{Add, And, Bra, Cmp, Hlt, Iff, Ldr, Mov, Mul, Orr, Rti, Shf, Str, Sub, Sys, Xor};
+use isa::{Cond, Encoding, Instruction, Op2};
 
 use crate::lex::LexemeError;
 use crate::{lex, uarch};
@@ -10,55 +12,59 @@ mod add;
 mod and;
 mod bra;
 mod cmp;
+mod hlt;
+mod iff;
 mod ldr;
 mod mov;
 mod mul;
 mod orr;
+mod rti;
 mod shf;
 mod str;
 mod sub;
+mod sys;
 mod xor;
 
-use self::add::Add;
-use self::and::And;
-use self::bra::Bra;
-use self::cmp::Cmp;
-use self::ldr::Ldr;
-use self::mov::Mov;
-use self::mul::Mul;
-use self::orr::Orr;
-use self::shf::Shf;
-use self::str::Str;
-use self::sub::Sub;
-use self::xor::Xor;
-
-trait Instruction: Debug + Display + FromStr {}
-
-pub fn asm(line: &[String]) -> Result<uarch, Box<dyn Error>> {
-    Ok(match &*line[0] {
-        "add" => line.join(" ").parse::<Add>()?.into(),
-        "and" => line.join(" ").parse::<And>()?.into(),
-        "call" | "goto" => line.join(" ").parse::<Bra>()?.into(),
-        "cmp" | "cmn" | "tst" | "teq" => line.join(" ").parse::<Cmp>()?.into(),
-        "ldr" | "pop" => line.join(" ").parse::<Ldr>()?.into(),
-        "mov" | "neg" | "not" => line.join(" ").parse::<Mov>()?.into(),
-        "mul" => line.join(" ").parse::<Mul>()?.into(),
-        "orr" => line.join(" ").parse::<Orr>()?.into(),
-        "lsr" | "asr" | "ror" | "lsl" | "asl" | "rol" => line.join(" ").parse::<Shf>()?.into(),
-        "str" | "push" => line.join(" ").parse::<Str>()?.into(),
-        "sub" | "rsb" => line.join(" ").parse::<Sub>()?.into(),
-        "xor" => line.join(" ").parse::<Xor>()?.into(),
+trait Parse: Sized {
+    type Err;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err>;
+}
+
+pub fn asm(line: &[String], enc: Encoding) -> Result<uarch, Box<dyn Error>> {
+    let instr = match &*line[0] {
+        "add" => Instruction::Add(Add::from_str(&line.join(" "))?),
+        "and" => Instruction::And(And::from_str(&line.join(" "))?),
+        "call" | "goto" => Instruction::Bra(Bra::from_str(&line.join(" "))?),
+        "cmp" | "cmn" | "tst" | "teq" => Instruction::Cmp(Cmp::from_str(&line.join(" "))?),
+        "hlt" => Instruction::Hlt(Hlt::from_str(&line.join(" "))?),
+        "ldr" | "pop" => Instruction::Ldr(Ldr::from_str(&line.join(" "))?),
+        "mov" | "neg" | "not" => Instruction::Mov(Mov::from_str(&line.join(" "))?),
+        "mul" => Instruction::Mul(Mul::from_str(&line.join(" "))?),
+        "orr" => Instruction::Orr(Orr::from_str(&line.join(" "))?),
+        "rti" => Instruction::Rti(Rti::from_str(&line.join(" "))?),
+        "lsr" | "asr" | "ror" | "lsl" | "asl" | "rol" => {
+            Instruction::Shf(Shf::from_str(&line.join(" "))?)
+        }
+        "str" | "push" => Instruction::Str(Str::from_str(&line.join(" "))?),
+        "sub" | "rsb" => Instruction::Sub(Sub::from_str(&line.join(" "))?),
+        "sys" => Instruction::Sys(Sys::from_str(&line.join(" "))?),
+        "xor" => Instruction::Xor(Xor::from_str(&line.join(" "))?),
+        op if op.starts_with("if") => Instruction::Iff(Iff::from_str(&line.join(" "))?),
+        op if legacy_branch(op) => Instruction::Bra(Bra::from_str(&line.join(" "))?),
         _ => return Err(InstructionError::UnknownInstruction.into()),
-    })
+    };
+    Ok(instr.encode(enc)?)
 }
 
-#[derive(Debug)]
-enum Op2 {
-    Reg(uarch),
-    Imm(uarch),
+/// Checks if a mnemonic names a legacy branch, as in `b{l}{cond}`.
+fn legacy_branch(op: &str) -> bool {
+    let cond = |cond| Cond::from_str(cond).is_ok();
+    op.strip_prefix('b')
+        .is_some_and(|op| cond(op) || op.strip_prefix('l').is_some_and(cond))
 }
 
-impl FromStr for Op2 {
+impl Parse for Op2 {
     type Err = LexemeError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -69,6 +75,31 @@ impl FromStr for Op2 {
     }
 }
 
+impl Parse for Cond {
+    type Err = InstructionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            // Omitted conditions always pass
+            "" | "al" => Cond::Al,
+            "nv" => Cond::Nv,
+            "eq" => Cond::Eq,
+            "ne" => Cond::Ne,
+            "lt" => Cond::Lt,
+            "le" => Cond::Le,
+            "ge" => Cond::Ge,
+            "gt" => Cond::Gt,
+            "cc" => Cond::Cc,
+            "cs" => Cond::Cs,
+            "vc" => Cond::Vc,
+            "vs" => Cond::Vs,
+            "pl" => Cond::Pl,
+            "mi" => Cond::Mi,
+            _ => return Err(InstructionError::BadInstruction),
+        })
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum InstructionError {
     EmptyStr,
@@ -99,3 +130,21 @@ impl Display for InstructionError {
 }
 
 impl Error for InstructionError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn asm(line: &str) -> Result<uarch, Box<dyn Error>> {
+        super::asm(&lex::tokenize(line).unwrap(), Encoding::V1)
+    }
+
+    #[test]
+    fn legacy_branch() {
+        assert_eq!(asm("b 0x2").unwrap(), 0x0081);
+        assert!(asm("bl 0x2").is_ok());
+        // Ensure other names are unknown, rather than bad branches
+        let err = asm("bogus r0").unwrap_err();
+        assert_eq!(err.to_string(), "Unknown instruction");
+    }
+}
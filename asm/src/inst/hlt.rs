@@ -0,0 +1,31 @@
+use std::cmp::Ordering;
+use std::error::Error;
+
+use isa::inst::hlt::Hlt;
+
+use super::{InstructionError, Parse};
+use crate::lex;
+
+impl Parse for Hlt {
+    type Err = Box<dyn Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // Only operate on lowercase strings
+        // (also creates an owned String from &str)
+        let s = s.to_lowercase();
+        // Split into constituent tokens
+        let tokens = lex::tokenize(&s).ok_or(InstructionError::EmptyStr)?;
+        // Ensure correct number of tokens
+        match tokens.len().cmp(&1) {
+            Ordering::Less => Err(InstructionError::MissingOps),
+            Ordering::Equal => Ok(()),
+            Ordering::Greater => Err(InstructionError::ExtraOps),
+        }?;
+        // Check instruction is correct
+        (tokens[0] == "hlt")
+            .then_some(())
+            .ok_or(InstructionError::BadInstruction)?;
+        // Create Self from parts
+        Ok(Self)
+    }
+}
@@ -2,6 +2,7 @@ use std::error::Error;
 use std::fmt::{self, Display};
 use std::result;
 
+use isa::reg;
 use lazy_static::lazy_static;
 use regex::Regex;
 
@@ -29,13 +30,26 @@ pub fn parse_reg(token: &str) -> Result<uarch> {
     lazy_static! {
         static ref RE: Regex = Regex::new(r"^r(\d+)$").unwrap();
     }
-    (|| match token {
-        "sp" => Some(13),
-        "lr" => Some(14),
-        "pc" => Some(15),
-        token => uarch::from_str_radix(RE.captures(token)?.get(1)?.as_str(), 10).ok(),
-    })()
-    .ok_or_else(|| LexemeError::InvalidReg(token.to_string()))
+    // Look for an alias
+    if let Some(reg) = reg::lookup(token) {
+        return Ok(reg);
+    }
+    // Look for a register number
+    let num = RE
+        .captures(token)
+        .and_then(|captures| captures.get(1))
+        .ok_or_else(|| LexemeError::InvalidReg(token.to_string()))?;
+    match num.as_str().parse::<usize>() {
+        Ok(num) if num < reg::COUNT => Ok(num as uarch),
+        _ => Err(LexemeError::RegOutOfRange(token.to_string())),
+    }
+}
+
+pub fn is_symbol(token: &str) -> bool {
+    lazy_static! {
+        static ref RE: Regex = Regex::new(r"^[[:alpha:]_][[:word:]]*$").unwrap();
+    }
+    RE.is_match(token) && matches!(parse_reg(token), Err(LexemeError::InvalidReg(_)))
 }
 
 pub fn parse_imm(token: &str) -> Result<uarch> {
@@ -59,6 +73,7 @@ pub fn parse_imm(token: &str) -> Result<uarch> {
 pub enum LexemeError {
     EmptyToken,
     InvalidReg(String),
+    RegOutOfRange(String),
     InvalidImm(String),
 }
 
@@ -70,6 +85,9 @@ impl Display for LexemeError {
             match self {
                 Self::EmptyToken => "Found empty token; expected content".to_string(),
                 Self::InvalidReg(token) => format!("Could not parse register from `{}`", token),
+                Self::RegOutOfRange(token) => {
+                    format!("Register `{}` is out of range; expected r0 to r15", token)
+                }
                 Self::InvalidImm(token) => format!("Could not parse immediate from `{}`", token),
             }
         )
@@ -77,3 +95,23 @@ impl Display for LexemeError {
 }
 
 impl Error for LexemeError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reg() {
+        assert_eq!(parse_reg("a0").unwrap(), 0);
+        assert_eq!(parse_reg("g3").unwrap(), 7);
+        assert_eq!(parse_reg("g8").unwrap(), 12);
+        assert_eq!(parse_reg("sp").unwrap(), 13);
+        assert_eq!(parse_reg("r15").unwrap(), 15);
+        assert!(matches!(
+            parse_reg("r16"),
+            Err(LexemeError::RegOutOfRange(_))
+        ));
+        assert!(matches!(parse_reg("g9"), Err(LexemeError::InvalidReg(_))));
+        assert!(!is_symbol("r16"));
+    }
+}
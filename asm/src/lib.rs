@@ -5,33 +5,30 @@
 use std::error::Error;
 use std::fmt::{self, Display};
 use std::fs::File;
-use std::io::{self, BufRead, BufReader, Write};
-use std::mem;
+use std::io::{self, Write};
 use std::path::{Path, PathBuf};
 
 use colored::Colorize;
-use line::Line;
+use isa::{iarch, uarch, Encoding, WORDSIZE};
 
 mod inst;
 mod lex;
 mod line;
+pub mod link;
+pub mod obj;
 mod prep;
 mod scope;
 mod unit;
-mod util;
 
+use crate::obj::Object;
 use crate::unit::Unit;
 
-#[allow(non_camel_case_types)]
-type iarch = i16;
-#[allow(non_camel_case_types)]
-type uarch = u16;
-
-const WORDSIZE: usize = mem::size_of::<uarch>();
-
 #[derive(Debug, Default)]
 pub struct Assembler {
+    enc: Encoding,
+    paths: Vec<PathBuf>,
     units: Vec<Unit>,
+    obj: Object,
     words: Vec<uarch>,
 }
 
@@ -40,56 +37,77 @@ impl Assembler {
         Default::default()
     }
 
+    pub fn set_encoding(&mut self, enc: Encoding) {
+        self.enc = enc;
+    }
+
+    /// Adds a directory to search for included files.
+    pub fn include(&mut self, path: &Path) {
+        self.paths.push(path.to_path_buf());
+    }
+
     pub fn src(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
-        // Open the input file
-        let f = File::open(&path)?;
-        // Read lines from file
-        let mut lines: Vec<Line> = BufReader::new(f)
-            .lines()
-            .collect::<Result<Vec<_>, _>>()?
-            .into_iter()
-            .enumerate()
-            .map(|(idx, line)| Line::new(idx, line))
-            .filter(|line| !line.tokens.is_empty())
-            .collect();
-        // Perform preprocessing
-        prep::prep(&mut lines);
-        // Create a unit for this file
-        let unit = Unit::new(path.to_path_buf(), lines);
-        // Ensure no duplicate symbols
-        // TODO
+        // Read and preprocess the input file
+        let lines = prep::prep(path, &self.paths)?;
         // Create translation unit
-        self.units.push(unit);
+        self.units.push(Unit::new(lines));
         Ok(())
     }
 
     pub fn asm(&mut self) -> Result<(), Box<dyn Error>> {
-        // Concatenate translation units
-        // TODO: keep track of source file when concatenating
-        let unit = self.units.pop().unwrap_or_else(|| Unit::default());
-        let unit = self
+        // Assemble each translation unit into an object
+        let objs = self
             .units
-            .clone()
-            .into_iter()
-            .try_fold(unit, Unit::concat)?;
-        // Assemble unit into binary
-        self.words = unit.asm()?;
+            .drain(..)
+            .map(|unit| unit.asm(self.enc))
+            .collect::<Result<Vec<_>, _>>()?;
+        // Combine into a single relocatable object
+        self.obj = link::merge(objs)?;
         Ok(())
     }
 
-    pub fn out(&self, out: &Path) -> io::Result<()> {
+    pub fn link(&mut self) -> Result<(), Box<dyn Error>> {
+        // Resolve the assembled object into an image
+        self.words = link::resolve(&self.obj)?;
+        Ok(())
+    }
+
+    pub fn obj(&self, out: &Path) -> io::Result<()> {
         // Write to the output file
         let mut f = File::create(out)?;
-        f.write_all(
-            &self
-                .words
-                .iter()
-                .flat_map(|word| word.to_le_bytes())
-                .collect::<Vec<_>>(),
-        )
+        f.write_all(&self.obj.to_bytes())
+    }
+
+    /// Returns the linked image as little-endian bytes.
+    pub fn bytes(&self) -> Vec<u8> {
+        self.words
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect()
+    }
+
+    pub fn out(&self, out: &Path) -> io::Result<()> {
+        write(out, &self.words)
+    }
+
+    /// Writes the linked image's symbol map.
+    pub fn map(&self, out: &Path) -> Result<(), Box<dyn Error>> {
+        Ok(link::write_map(out, &link::map(&self.obj)?)?)
     }
 }
 
+/// Writes an image to a file as little-endian words.
+fn write(out: &Path, words: &[uarch]) -> io::Result<()> {
+    // Write to the output file
+    let mut f = File::create(out)?;
+    f.write_all(
+        &words
+            .iter()
+            .flat_map(|word| word.to_le_bytes())
+            .collect::<Vec<_>>(),
+    )
+}
+
 #[derive(Debug)]
 pub struct AsmError {
     err: Box<dyn Error>,
@@ -1,4 +1,7 @@
+use std::path::{Path, PathBuf};
+
 use crate::lex;
+use crate::obj::TEXT;
 use crate::scope::Scope;
 
 #[derive(Clone, Debug)]
@@ -9,18 +12,26 @@ pub enum Source {
 
 #[derive(Clone, Debug)]
 pub struct Line {
+    pub file: PathBuf,
     pub number: usize,
     pub text: String,
     pub tokens: Vec<String>,
+    /// Section the line is assembled into.
+    pub section: String,
+    /// Tokens referring to labels, paired with the label's index.
+    pub refs: Vec<(usize, usize)>,
 }
 
 impl Line {
-    pub fn new(number: usize, text: String) -> Self {
-        let tokens = lex::tokenize(&text).unwrap_or_else(Vec::new);
+    pub fn new(file: &Path, number: usize, text: String) -> Self {
+        let tokens = lex::tokenize(&text).unwrap_or_default();
         Self {
+            file: file.to_path_buf(),
             number,
             text,
             tokens,
+            section: TEXT.to_string(),
+            refs: Vec::new(),
         }
     }
 }
@@ -0,0 +1,335 @@
+//! Linking of relocatable objects.
+//!
+//! Linking happens in two steps. Objects are first merged into a single
+//! object, concatenating sections of the same name. The merged object is then
+//! laid out in memory, with `text` sections first and `data` sections after,
+//! and every relocation is patched with its symbol's final address.
+//!
+//! Images begin with a reset vector, holding the address of the entry point.
+//! This is the location marked by `.entry`, or the start of `text` otherwise.
+//! Images in the legacy v0 encoding have no reset vector, and are entered at
+//! their start instead.
+
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+
+use isa::inst::Bra;
+use isa::{iarch, uarch, Encoding, Instruction, Op2, WORDSIZE};
+
+use crate::obj::{Binding, Kind, Object, DATA, ENTRY, TEXT};
+
+#[derive(Debug, Default)]
+pub struct Linker {
+    objs: Vec<Object>,
+    obj: Object,
+    words: Vec<uarch>,
+}
+
+impl Linker {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds an object file to be linked.
+    pub fn load(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let obj = Object::from_bytes(&fs::read(path)?)?;
+        self.add(obj);
+        Ok(())
+    }
+
+    /// Adds an object to be linked.
+    pub fn add(&mut self, obj: Object) {
+        self.objs.push(obj);
+    }
+
+    pub fn link(&mut self) -> Result<(), LinkError> {
+        self.obj = merge(self.objs.drain(..))?;
+        self.words = resolve(&self.obj)?;
+        Ok(())
+    }
+
+    pub fn out(&self, out: &Path) -> io::Result<()> {
+        crate::write(out, &self.words)
+    }
+
+    /// Writes the linked image's symbol map.
+    pub fn map(&self, out: &Path) -> Result<(), Box<dyn Error>> {
+        Ok(write_map(out, &map(&self.obj)?)?)
+    }
+}
+
+/// Merges objects into a single relocatable object.
+pub fn merge(objs: impl IntoIterator<Item = Object>) -> Result<Object, LinkError> {
+    let mut objs = objs.into_iter().peekable();
+    let mut out = Object {
+        enc: objs.peek().map(|obj| obj.enc).unwrap_or_default(),
+        ..Default::default()
+    };
+    for obj in objs {
+        // Ensure encodings agree
+        if obj.enc != out.enc {
+            return Err(LinkError::EncodingMismatch);
+        }
+        // Append sections, noting where each one lands
+        let sections: Vec<_> = obj
+            .sections
+            .into_iter()
+            .map(|sec| {
+                let idx = out.section(&sec.name);
+                let words = &mut out.sections[idx].words;
+                let base = words.len() * WORDSIZE;
+                words.extend(sec.words);
+                (idx, base)
+            })
+            .collect();
+        // Append symbols
+        let base = out.symbols.len();
+        for mut sym in obj.symbols {
+            sym.def = sym
+                .def
+                .map(|(sec, off)| (sections[sec].0, sections[sec].1 + off));
+            // Ensure no duplicate global symbols
+            let dup = sym.def.is_some()
+                && sym.binding == Binding::Global
+                && out.symbols.iter().any(|other| {
+                    other.def.is_some()
+                        && other.binding == Binding::Global
+                        && other.name == sym.name
+                });
+            if dup {
+                return Err(LinkError::DuplicateSymbol(sym.name));
+            }
+            out.symbols.push(sym);
+        }
+        // Append relocations
+        out.relocs.extend(obj.relocs.into_iter().map(|mut reloc| {
+            (reloc.section, reloc.offset) = (
+                sections[reloc.section].0,
+                sections[reloc.section].1 + reloc.offset,
+            );
+            reloc.symbol += base;
+            reloc
+        }));
+    }
+    Ok(out)
+}
+
+/// Lays out an object in memory, producing an image.
+pub fn resolve(obj: &Object) -> Result<Vec<uarch>, LinkError> {
+    let (mut words, bases) = layout(obj);
+    let addrs = addrs(obj, &bases)?;
+    // Point the reset vector at the entry
+    let entry = obj
+        .symbols
+        .iter()
+        .zip(&addrs)
+        .find(|(sym, _)| sym.name == ENTRY && sym.def.is_some())
+        .map(|(_, &addr)| addr);
+    match obj.enc {
+        Encoding::V0 if entry.unwrap_or(0) != 0 => return Err(LinkError::BadEntry),
+        Encoding::V0 => (),
+        _ => words[0] = entry.unwrap_or(WORDSIZE) as uarch,
+    }
+    // Apply relocations
+    for reloc in &obj.relocs {
+        let addr = bases[reloc.section] + reloc.offset;
+        let word = &mut words[addr / WORDSIZE];
+        let target = addrs[reloc.symbol];
+        let name = || obj.symbols[reloc.symbol].name.clone();
+        match reloc.kind {
+            Kind::Abs => *word = word.wrapping_add(target as uarch),
+            Kind::Pc => {
+                let delta = target as iarch - (addr + WORDSIZE) as iarch;
+                let instr = match Instruction::decode(*word, obj.enc) {
+                    Ok(Instruction::Bra(instr)) => {
+                        // Ensure the displacement can be encoded
+                        if !(-0x80..0x80).contains(&delta) {
+                            return Err(LinkError::OutOfRange(name()));
+                        }
+                        Instruction::Bra(Bra {
+                            op2: Op2::Imm(delta as uarch),
+                            ..instr
+                        })
+                    }
+                    _ => return Err(LinkError::BadRelocation(name())),
+                };
+                *word = instr
+                    .encode(obj.enc)
+                    .map_err(|_| LinkError::BadRelocation(name()))?;
+            }
+        }
+    }
+    Ok(words)
+}
+
+/// Lists the address of every named symbol once laid out in memory.
+pub fn map(obj: &Object) -> Result<Vec<(String, uarch)>, LinkError> {
+    let (_, bases) = layout(obj);
+    let addrs = addrs(obj, &bases)?;
+    let mut map: Vec<_> = obj
+        .symbols
+        .iter()
+        .zip(addrs)
+        .filter(|(sym, _)| sym.def.is_some() && !sym.name.starts_with(['.', '=']))
+        .map(|(sym, addr)| (sym.name.clone(), addr as uarch))
+        .collect();
+    map.sort_by_key(|&(_, addr)| addr);
+    Ok(map)
+}
+
+/// Places sections after the reset vector, if any, returning the image and the
+/// base address of each section.
+fn layout(obj: &Object) -> (Vec<uarch>, Vec<usize>) {
+    // Order sections by placement
+    let mut order: Vec<_> = (0..obj.sections.len()).collect();
+    order.sort_by_key(|&idx| match &*obj.sections[idx].name {
+        TEXT => 0,
+        DATA => 1,
+        _ => 2,
+    });
+    // Lay out sections after the reset vector
+    let mut words: Vec<uarch> = match obj.enc {
+        Encoding::V0 => Vec::new(),
+        _ => vec![0],
+    };
+    let mut bases = vec![0; obj.sections.len()];
+    for idx in order {
+        bases[idx] = words.len() * WORDSIZE;
+        words.extend(&obj.sections[idx].words);
+    }
+    (words, bases)
+}
+
+/// Computes the address of each symbol.
+fn addrs(obj: &Object, bases: &[usize]) -> Result<Vec<usize>, LinkError> {
+    obj.symbols
+        .iter()
+        .map(|sym| {
+            sym.def
+                .or_else(|| {
+                    // Look for a global definition elsewhere
+                    obj.symbols
+                        .iter()
+                        .filter(|other| other.binding == Binding::Global && other.name == sym.name)
+                        .find_map(|other| other.def)
+                })
+                .map(|(sec, off)| bases[sec] + off)
+                .ok_or_else(|| LinkError::UndefinedSymbol(sym.name.clone()))
+        })
+        .collect()
+}
+
+/// Writes a symbol map to a file, one address and name per line.
+pub fn write_map(out: &Path, map: &[(String, uarch)]) -> io::Result<()> {
+    let mut f = File::create(out)?;
+    for (name, addr) in map {
+        writeln!(f, "{:#06x} {}", addr, name)?;
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum LinkError {
+    EncodingMismatch,
+    DuplicateSymbol(String),
+    UndefinedSymbol(String),
+    OutOfRange(String),
+    BadRelocation(String),
+    /// The entry point of a v0 image is not at its start.
+    BadEntry,
+}
+
+impl Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::EncodingMismatch => "Objects use different encodings".to_string(),
+                Self::DuplicateSymbol(name) => format!("Duplicate symbol `{}`", name),
+                Self::UndefinedSymbol(name) => format!("Undefined symbol `{}`", name),
+                Self::OutOfRange(name) => format!("Symbol `{}` is out of range", name),
+                Self::BadRelocation(name) => format!("Cannot relocate reference to `{}`", name),
+                Self::BadEntry => {
+                    "Entry point must be at the start of a v0 image".to_string()
+                }
+            }
+        )
+    }
+}
+
+impl Error for LinkError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::obj::{Reloc, Section, Symbol};
+
+    fn object(text: Vec<uarch>, symbols: Vec<Symbol>, relocs: Vec<Reloc>) -> Object {
+        Object {
+            sections: vec![Section {
+                name: TEXT.to_string(),
+                words: text,
+            }],
+            symbols,
+            relocs,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn link() {
+        // goto _foo; hlt
+        let main = object(
+            vec![0x0080, 0x0c00],
+            vec![Symbol {
+                name: "_foo".to_string(),
+                binding: Binding::Global,
+                def: None,
+            }],
+            vec![Reloc {
+                section: 0,
+                offset: 0,
+                kind: Kind::Pc,
+                symbol: 0,
+            }],
+        );
+        // _foo: hlt
+        let foo = object(
+            vec![0x0c00],
+            vec![Symbol {
+                name: "_foo".to_string(),
+                binding: Binding::Global,
+                def: Some((0, 0)),
+            }],
+            vec![],
+        );
+        let obj = merge([main, foo.clone()]).unwrap();
+        assert_eq!(resolve(&obj).unwrap(), vec![0x0002, 0x0081, 0x0c00, 0x0c00]);
+        assert_eq!(map(&obj).unwrap(), vec![("_foo".to_string(), 0x0006)]);
+        // Ensure duplicates are rejected
+        assert!(matches!(
+            merge([foo.clone(), foo]),
+            Err(LinkError::DuplicateSymbol(_))
+        ));
+        // Ensure v0 images have no reset vector
+        let entry = |offset| Symbol {
+            name: ENTRY.to_string(),
+            binding: Binding::Local,
+            def: Some((0, offset)),
+        };
+        let legacy = Object {
+            enc: Encoding::V0,
+            ..object(vec![0x0000, 0x0000], vec![entry(0)], vec![])
+        };
+        assert_eq!(resolve(&legacy).unwrap(), vec![0x0000, 0x0000]);
+        let legacy = Object {
+            enc: Encoding::V0,
+            ..object(vec![0x0000, 0x0000], vec![entry(2)], vec![])
+        };
+        assert!(matches!(resolve(&legacy), Err(LinkError::BadEntry)));
+    }
+}
@@ -1,9 +1,10 @@
 use std::path::PathBuf;
 use std::process;
 
-use asm::Assembler;
+use asm::{AsmError, Assembler};
 use clap::{Parser, ValueHint};
 use env_logger as logger;
+use isa::Encoding;
 use log::error;
 
 fn main() {
@@ -19,10 +20,14 @@ fn main() {
 
     // Instantiate an assembler
     let mut a = Assembler::new();
+    a.set_encoding(args.encoding);
+    for path in &args.include {
+        a.include(path);
+    }
     // Source each input file
     for file in &args.srcs {
         a.src(file).unwrap_or_else(|err| {
-            eprintln!("{}: `{}`", err, file.display());
+            eprintln!("{}", err);
             process::exit(1);
         });
     }
@@ -31,11 +36,29 @@ fn main() {
         eprintln!("{}", err);
         process::exit(1);
     });
+    // Link unless producing an object
+    if !args.compile {
+        a.link().unwrap_or_else(|err| {
+            eprintln!("{}", AsmError::from(err));
+            process::exit(1);
+        });
+    }
     // Write output file
-    a.out(&args.out).unwrap_or_else(|err| {
+    match args.compile {
+        true => a.obj(&args.out),
+        false => a.out(&args.out),
+    }
+    .unwrap_or_else(|err| {
         error!("{}: `{}`", err, &args.out.display());
         process::exit(1);
     });
+    // Write symbol map
+    if let Some(map) = &args.map {
+        a.map(map).unwrap_or_else(|err| {
+            error!("{}: `{}`", err, map.display());
+            process::exit(1);
+        });
+    }
 }
 
 /// Assembler for the KAP-16 processor.
@@ -55,6 +78,28 @@ struct Args {
     #[clap(value_hint = ValueHint::FilePath)]
     out: PathBuf,
 
+    /// Add a directory to search for included files
+    #[clap(short = 'I', long)]
+    #[clap(parse(from_os_str))]
+    #[clap(value_hint = ValueHint::DirPath)]
+    include: Vec<PathBuf>,
+
+    /// Output a relocatable object instead of linking
+    #[clap(short)]
+    compile: bool,
+
+    /// Instruction encoding (v0, v1)
+    #[clap(long)]
+    #[clap(default_value = "v1")]
+    encoding: Encoding,
+
+    /// Output a symbol map for debugging
+    #[clap(short, long)]
+    #[clap(parse(from_os_str))]
+    #[clap(value_hint = ValueHint::FilePath)]
+    #[clap(conflicts_with = "compile")]
+    map: Option<PathBuf>,
+
     /// Use verbose output (-v, -vv, -vvv, etc.)
     #[clap(short, long)]
     #[clap(parse(from_occurrences))]
@@ -0,0 +1,349 @@
+//! Relocatable object files.
+//!
+//! Objects hold assembled sections which have not yet been placed in memory,
+//! along with the symbols they define or reference and the fixups needed to
+//! patch in symbol addresses once they are known.
+//!
+//! # Format
+//!
+//! All integers are stored little-endian. Strings are stored as a `u16`
+//! length followed by that many UTF-8 bytes.
+//!
+//! | Field    | Contents                                          |
+//! | -------- | ------------------------------------------------- |
+//! | Header   | magic `KOBJ`, `u16` version, `u8` encoding        |
+//! | Sections | `u16` count; each a name, `u16` length, words     |
+//! | Symbols  | `u16` count; each a name, `u8` binding, location  |
+//! | Relocs   | `u16` count; each a location, `u8` kind, `u16` id |
+//!
+//! A location is a `u16` section index and a `u16` byte offset within it.
+//! Undefined symbols use the section index `0xffff`.
+
+use std::error::Error;
+use std::fmt::{self, Display};
+
+use isa::{uarch, Encoding, WORDSIZE};
+
+const MAGIC: &[u8; 4] = b"KOBJ";
+const VERSION: u16 = 1;
+const UNDEFINED: u16 = 0xffff;
+
+/// Name of the section holding instructions.
+pub const TEXT: &str = "text";
+/// Name of the section holding data.
+pub const DATA: &str = "data";
+/// Name of the symbol marking the program's entry point.
+pub const ENTRY: &str = ".entry";
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Object {
+    pub enc: Encoding,
+    pub sections: Vec<Section>,
+    pub symbols: Vec<Symbol>,
+    pub relocs: Vec<Reloc>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Section {
+    pub name: String,
+    pub words: Vec<uarch>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Symbol {
+    pub name: String,
+    pub binding: Binding,
+    /// Section index and byte offset, if defined in this object.
+    pub def: Option<(usize, usize)>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Binding {
+    /// Only visible within its own object.
+    Local = 0,
+    /// Visible to every object being linked.
+    Global = 1,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Reloc {
+    /// Section index of the word to patch.
+    pub section: usize,
+    /// Byte offset of the word to patch within its section.
+    pub offset: usize,
+    pub kind: Kind,
+    /// Index of the referenced symbol.
+    pub symbol: usize,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Kind {
+    /// Branch displacement relative to the following instruction.
+    Pc = 0,
+    /// Absolute address, added to the word's contents.
+    Abs = 1,
+}
+
+impl Object {
+    /// Finds the index of a section by name, creating it if needed.
+    pub fn section(&mut self, name: &str) -> usize {
+        match self.sections.iter().position(|sec| sec.name == name) {
+            Some(idx) => idx,
+            None => {
+                self.sections.push(Section {
+                    name: name.to_string(),
+                    words: Vec::new(),
+                });
+                self.sections.len() - 1
+            }
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Writer::default();
+        // Write header
+        buf.0.extend(MAGIC);
+        buf.u16(VERSION);
+        buf.u8(match self.enc {
+            Encoding::V0 => 0,
+            Encoding::V1 => 1,
+        });
+        // Write sections
+        buf.u16(self.sections.len() as u16);
+        for sec in &self.sections {
+            buf.str(&sec.name);
+            buf.u16(sec.words.len() as u16);
+            sec.words.iter().for_each(|&word| buf.u16(word));
+        }
+        // Write symbols
+        buf.u16(self.symbols.len() as u16);
+        for sym in &self.symbols {
+            buf.str(&sym.name);
+            buf.u8(sym.binding as u8);
+            let (section, offset) = sym.def.unwrap_or((UNDEFINED as usize, 0));
+            buf.u16(section as u16);
+            buf.u16(offset as u16);
+        }
+        // Write relocations
+        buf.u16(self.relocs.len() as u16);
+        for reloc in &self.relocs {
+            buf.u16(reloc.section as u16);
+            buf.u16(reloc.offset as u16);
+            buf.u8(reloc.kind as u8);
+            buf.u16(reloc.symbol as u16);
+        }
+        buf.0
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ObjectError> {
+        let mut buf = Reader(bytes);
+        // Read header
+        (buf.take(MAGIC.len())? == MAGIC)
+            .then_some(())
+            .ok_or(ObjectError::BadMagic)?;
+        let version = buf.u16()?;
+        (version == VERSION)
+            .then_some(())
+            .ok_or(ObjectError::Unsupported(version))?;
+        let enc = match buf.u8()? {
+            0 => Encoding::V0,
+            1 => Encoding::V1,
+            _ => return Err(ObjectError::Malformed),
+        };
+        // Read sections
+        let sections = (0..buf.u16()?)
+            .map(|_| {
+                let name = buf.str()?;
+                let words = (0..buf.u16()?)
+                    .map(|_| buf.u16())
+                    .collect::<Result<_, _>>()?;
+                Ok(Section { name, words })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        // Read symbols
+        let symbols = (0..buf.u16()?)
+            .map(|_| {
+                let name = buf.str()?;
+                let binding = match buf.u8()? {
+                    0 => Binding::Local,
+                    1 => Binding::Global,
+                    _ => return Err(ObjectError::Malformed),
+                };
+                let section = buf.u16()?;
+                let offset = buf.u16()? as usize;
+                let def = match section {
+                    UNDEFINED => None,
+                    section if (section as usize) < sections.len() => {
+                        Some((section as usize, offset))
+                    }
+                    _ => return Err(ObjectError::Malformed),
+                };
+                Ok(Symbol { name, binding, def })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        // Read relocations
+        let relocs = (0..buf.u16()?)
+            .map(|_| {
+                let section = buf.u16()? as usize;
+                let offset = buf.u16()? as usize;
+                let kind = match buf.u8()? {
+                    0 => Kind::Pc,
+                    1 => Kind::Abs,
+                    _ => return Err(ObjectError::Malformed),
+                };
+                let symbol = buf.u16()? as usize;
+                // Ensure relocations are in bounds
+                let words = sections
+                    .get(section)
+                    .ok_or(ObjectError::Malformed)?
+                    .words
+                    .len();
+                (offset / WORDSIZE < words && symbol < symbols.len())
+                    .then_some(())
+                    .ok_or(ObjectError::Malformed)?;
+                Ok(Reloc {
+                    section,
+                    offset,
+                    kind,
+                    symbol,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        // Ensure nothing is left over
+        buf.0
+            .is_empty()
+            .then_some(())
+            .ok_or(ObjectError::Malformed)?;
+        Ok(Self {
+            enc,
+            sections,
+            symbols,
+            relocs,
+        })
+    }
+}
+
+#[derive(Default)]
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.0.extend(value.to_le_bytes());
+    }
+
+    fn str(&mut self, value: &str) {
+        self.u16(value.len() as u16);
+        self.0.extend(value.as_bytes());
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ObjectError> {
+        (len <= self.0.len())
+            .then_some(())
+            .ok_or(ObjectError::Truncated)?;
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, ObjectError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, ObjectError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn str(&mut self) -> Result<String, ObjectError> {
+        let len = self.u16()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| ObjectError::Malformed)
+    }
+}
+
+#[derive(Debug)]
+pub enum ObjectError {
+    BadMagic,
+    Unsupported(u16),
+    Truncated,
+    Malformed,
+}
+
+impl Display for ObjectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::BadMagic => "Not an object file".to_string(),
+                Self::Unsupported(version) => format!("Unsupported object version {}", version),
+                Self::Truncated => "Object file is truncated".to_string(),
+                Self::Malformed => "Object file is malformed".to_string(),
+            }
+        )
+    }
+}
+
+impl Error for ObjectError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let obj = Object {
+            enc: Encoding::V1,
+            sections: vec![
+                Section {
+                    name: TEXT.to_string(),
+                    words: vec![0x0080, 0x0c00],
+                },
+                Section {
+                    name: DATA.to_string(),
+                    words: vec![0x0000],
+                },
+            ],
+            symbols: vec![
+                Symbol {
+                    name: "_main".to_string(),
+                    binding: Binding::Global,
+                    def: Some((0, 0)),
+                },
+                Symbol {
+                    name: "_foo".to_string(),
+                    binding: Binding::Global,
+                    def: None,
+                },
+            ],
+            relocs: vec![
+                Reloc {
+                    section: 0,
+                    offset: 0,
+                    kind: Kind::Pc,
+                    symbol: 1,
+                },
+                Reloc {
+                    section: 1,
+                    offset: 0,
+                    kind: Kind::Abs,
+                    symbol: 0,
+                },
+            ],
+        };
+        let bytes = obj.to_bytes();
+        assert_eq!(Object::from_bytes(&bytes).unwrap(), obj);
+        // Ensure truncation is detected
+        assert!(matches!(
+            Object::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(ObjectError::Truncated)
+        ));
+    }
+}
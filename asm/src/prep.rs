@@ -1,3 +1,313 @@
+//! Source preprocessing.
+//!
+//! Preprocessing expands the following directives before assembly:
+//! - `.define NAME value`: substitutes `value` wherever the token `NAME` is
+//!   used afterwards.
+//! - `.include "file"`: inserts the lines of another file, searching relative
+//!   to the including file, then each include path in turn.
+//! - `.macro NAME [param, ...]` ... `.endm`: defines a macro, which expands to
+//!   its body when invoked as `NAME [arg, ...]`, substituting each parameter.
+//! - `.repeat N` ... `.end`: repeats its body `N` times.
+//!
+//! Expanded lines keep the file and line number they were written at.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::vec::IntoIter;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
 use crate::line::Line;
+use crate::{lex, AsmError, VerboseError};
+
+/// Maximum nesting of includes and macro expansions.
+const MAXDEPTH: usize = 0x40;
+
+#[derive(Clone, Debug)]
+struct Macro {
+    params: Vec<String>,
+    body: Vec<Line>,
+}
+
+#[derive(Debug, Default)]
+struct Prep<'a> {
+    paths: &'a [PathBuf],
+    defines: HashMap<String, Vec<String>>,
+    macros: HashMap<String, Macro>,
+    depth: usize,
+}
+
+/// Reads and preprocesses a source file.
+pub fn prep(path: &Path, paths: &[PathBuf]) -> Result<Vec<Line>, Box<dyn Error>> {
+    // Read lines from file
+    let lines = read(path).map_err(|err| AsmError::from(Box::from(err)))?;
+    // Perform preprocessing
+    let mut prep = Prep {
+        paths,
+        ..Default::default()
+    };
+    Ok(prep.lines(lines)?)
+}
+
+fn read(path: &Path) -> Result<Vec<Line>, PrepError> {
+    let read = || -> io::Result<_> {
+        BufReader::new(File::open(path)?)
+            .lines()
+            .collect::<Result<Vec<_>, _>>()
+    };
+    Ok(read()
+        .map_err(|err| PrepError::Read(path.to_path_buf(), err))?
+        .into_iter()
+        .enumerate()
+        .map(|(idx, text)| Line::new(path, idx + 1, text))
+        .filter(|line| !line.tokens.is_empty())
+        .collect())
+}
+
+impl<'a> Prep<'a> {
+    fn lines(&mut self, lines: Vec<Line>) -> Result<Vec<Line>, VerboseError> {
+        let mut out = Vec::new();
+        let mut iter = lines.into_iter();
+        while let Some(mut line) = iter.next() {
+            self.line(&mut line, &mut iter, &mut out).map_err(|err| {
+                match err.downcast::<VerboseError>() {
+                    // Keep the location of errors in expanded lines
+                    Ok(err) => *err,
+                    Err(err) => VerboseError {
+                        err: From::from(err),
+                        loc: (line.file.clone(), line.number),
+                        line: line.text.clone(),
+                    },
+                }
+            })?;
+        }
+        Ok(out)
+    }
+
+    fn line(
+        &mut self,
+        line: &mut Line,
+        iter: &mut IntoIter<Line>,
+        out: &mut Vec<Line>,
+    ) -> Result<(), Box<dyn Error>> {
+        let tokens: Vec<_> = line.tokens.iter().map(String::as_str).collect();
+        match tokens[..] {
+            [".", "define", name, ref value @ ..] => {
+                let value = self.subst(value);
+                self.defines.insert(name.to_string(), value);
+            }
+            [".", "include", ..] => {
+                lazy_static! {
+                    static ref RE: Regex = Regex::new(r#"^\s*\.include\s+"([^"]+)""#).unwrap();
+                }
+                let name = RE
+                    .captures(&line.text)
+                    .and_then(|captures| captures.get(1))
+                    .ok_or(PrepError::BadInclude)?
+                    .as_str();
+                let path = self.find(&line.file, name)?;
+                let lines = read(&path)?;
+                out.extend(self.nest(|prep| prep.lines(lines))?);
+            }
+            [".", "macro", name, ref params @ ..] => {
+                let params = params
+                    .split(|&token| token == ",")
+                    .filter(|param| !param.is_empty())
+                    .map(|param| match param {
+                        [param] => Ok(param.to_string()),
+                        _ => Err(PrepError::BadParam),
+                    })
+                    .collect::<Result<_, _>>()?;
+                let body = Self::body(iter, "endm")?;
+                self.macros.insert(name.to_string(), Macro { params, body });
+            }
+            [".", "repeat", ref count @ ..] => {
+                let count = match &self.subst(count)[..] {
+                    [count] => count
+                        .parse::<usize>()
+                        .or_else(|_| lex::parse_imm(count).map(usize::from))
+                        .map_err(|_| PrepError::BadCount(count.clone()))?,
+                    _ => return Err(PrepError::BadCount(count.join(" ")).into()),
+                };
+                let body = Self::body(iter, "end")?;
+                for _ in 0..count {
+                    out.extend(self.nest(|prep| prep.lines(body.clone()))?);
+                }
+            }
+            [name, ref args @ ..] if self.macros.contains_key(name) => {
+                let Macro { params, body } = self.macros[name].clone();
+                let args: Vec<_> = args
+                    .split(|&token| token == ",")
+                    .filter(|arg| !arg.is_empty())
+                    .collect();
+                if args.len() != params.len() {
+                    return Err(PrepError::BadArgs(params.len(), args.len()).into());
+                }
+                // Substitute parameters within the body
+                let body = body
+                    .into_iter()
+                    .map(|mut expanded| {
+                        expanded.tokens = expanded
+                            .tokens
+                            .iter()
+                            .flat_map(|token| {
+                                match params.iter().position(|param| param == token) {
+                                    Some(idx) => {
+                                        args[idx].iter().map(|arg| arg.to_string()).collect()
+                                    }
+                                    None => vec![token.clone()],
+                                }
+                            })
+                            .collect();
+                        expanded
+                    })
+                    .collect();
+                out.extend(self.nest(|prep| prep.lines(body))?);
+            }
+            _ => {
+                line.tokens = self.subst(&tokens);
+                out.push(line.clone());
+            }
+        }
+        Ok(())
+    }
+
+    /// Substitutes defined constants within tokens.
+    fn subst(&self, tokens: &[&str]) -> Vec<String> {
+        tokens
+            .iter()
+            .flat_map(|&token| match self.defines.get(token) {
+                Some(value) => value.clone(),
+                None => vec![token.to_string()],
+            })
+            .collect()
+    }
+
+    /// Collects lines up to the directive closing a block.
+    fn body(iter: &mut IntoIter<Line>, end: &str) -> Result<Vec<Line>, PrepError> {
+        let mut depth = 0;
+        let mut body = Vec::new();
+        for line in iter.by_ref() {
+            match line.tokens.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+                [".", "begin" | "func" | "repeat", ..] if end == "end" => depth += 1,
+                [".", directive] if directive == end => match depth {
+                    0 => return Ok(body),
+                    _ => depth -= 1,
+                },
+                _ => (),
+            }
+            body.push(line);
+        }
+        Err(PrepError::MissingEnd(end.to_string()))
+    }
+
+    /// Searches for an included file.
+    fn find(&self, file: &Path, name: &str) -> Result<PathBuf, PrepError> {
+        file.parent()
+            .into_iter()
+            .chain(self.paths.iter().map(PathBuf::as_path))
+            .map(|dir| dir.join(name))
+            .find(|path| path.is_file())
+            .ok_or_else(|| PrepError::NotFound(name.to_string()))
+    }
+
+    /// Runs a nested expansion, guarding against runaway recursion.
+    fn nest<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, VerboseError>,
+    ) -> Result<T, Box<dyn Error>> {
+        if self.depth == MAXDEPTH {
+            return Err(PrepError::TooDeep.into());
+        }
+        self.depth += 1;
+        let res = f(self);
+        self.depth -= 1;
+        Ok(res?)
+    }
+}
+
+#[derive(Debug)]
+pub enum PrepError {
+    Read(PathBuf, io::Error),
+    BadInclude,
+    NotFound(String),
+    BadParam,
+    BadArgs(usize, usize),
+    BadCount(String),
+    MissingEnd(String),
+    TooDeep,
+}
+
+impl Display for PrepError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Read(path, err) => format!("{}: `{}`", err, path.display()),
+                Self::BadInclude => "Expected a quoted file name".to_string(),
+                Self::NotFound(name) => format!("Could not find included file `{}`", name),
+                Self::BadParam => "Macro parameters must be single names".to_string(),
+                Self::BadArgs(expected, found) => {
+                    format!("Expected {} macro arguments; found {}", expected, found)
+                }
+                Self::BadCount(count) => format!("Could not parse repeat count from `{}`", count),
+                Self::MissingEnd(end) => format!("Missing closing `.{}`", end),
+                Self::TooDeep => "Expansion is nested too deeply".to_string(),
+            }
+        )
+    }
+}
+
+impl Error for PrepError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prep(src: &str) -> Result<Vec<Line>, VerboseError> {
+        let lines = src
+            .lines()
+            .enumerate()
+            .map(|(idx, text)| Line::new(Path::new("test.s"), idx + 1, text.to_string()))
+            .filter(|line| !line.tokens.is_empty())
+            .collect();
+        Prep::default().lines(lines)
+    }
+
+    fn tokens(lines: &[Line]) -> Vec<String> {
+        lines.iter().map(|line| line.tokens.join(" ")).collect()
+    }
+
+    #[test]
+    fn define() {
+        let lines = prep(".define TOP 0x4000\nldr sp, =TOP").unwrap();
+        assert_eq!(tokens(&lines), ["ldr sp , = 0x4000"]);
+        assert_eq!(lines[0].number, 2);
+    }
+
+    #[test]
+    fn repeat() {
+        let lines = prep(".repeat 0x3\n.begin\nadd a0, 0d1\n.end\n.end\nhlt").unwrap();
+        assert_eq!(tokens(&lines).len(), 10);
+        assert_eq!(lines[1].number, 3);
+        assert!(prep(".repeat 2\nhlt").is_err());
+    }
 
-pub fn prep(_lines: &mut Vec<Line>) {} // TODO
+    #[test]
+    fn macros() {
+        let src = ".macro inc reg, by\nadd reg, by\n.endm\ninc a0, 0d1\ninc g1, 0d2";
+        let lines = prep(src).unwrap();
+        assert_eq!(tokens(&lines), ["add a0 , 0d1", "add g1 , 0d2"]);
+        assert_eq!(lines[1].number, 2);
+        // Ensure argument counts are checked
+        assert!(prep(".macro nop\n.endm\nnop a0").is_err());
+        // Ensure recursion is bounded
+        assert!(prep(".macro f\nf\n.endm\nf").is_err());
+    }
+}
@@ -1,57 +1,106 @@
-use std::collections::HashMap;
-use std::mem;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
 use std::vec::IntoIter;
 
 use crate::line::{Line, Source};
-use crate::{WORDSIZE, iarch};
+use crate::obj::{ENTRY, TEXT};
 
 #[derive(Clone, Debug, Default)]
 pub struct Scope {
     pub source: Vec<Source>,
     pub symbols: HashMap<String, usize>,
+    pub globals: HashSet<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Label {
+    pub name: String,
+    pub index: usize,
+    pub global: bool,
 }
 
 impl Scope {
     pub fn new(lines: Vec<Line>) -> Self {
         let mut scope = Self::default();
         // Construct using an IntoIter
-        scope.ctor(&mut lines.into_iter());
+        scope.ctor(&mut lines.into_iter(), &mut TEXT.to_string());
         // Ensure we finished the file
         // TODO
+        // Place remaining literals at the end
+        let pool = Line::new(Path::new(""), 0, ".pool".to_string());
+        scope.source.push(Source::Line(pool));
         // Return the constructed scope
         scope
     }
 
-    fn ctor(&mut self, iter: &mut IntoIter<Line>) {
+    fn ctor(&mut self, iter: &mut IntoIter<Line>, section: &mut String) {
         // Construct from lines iterator
-        while let Some(line) = iter.next() {
+        while let Some(mut line) = iter.next() {
             match line
                 .tokens
                 .iter()
                 .map(String::as_str)
                 .collect::<Vec<&str>>()[..]
             {
-                [".", "begin" | "func"] => {
+                [".", "begin"] => {
+                    let mut scope = Self::default();
+                    scope.ctor(iter, section);
+                    self.source.push(Source::Scope(scope));
+                }
+                [".", "func"] => {
                     let mut scope = Self::default();
-                    scope.ctor(iter);
+                    scope.ctor(iter, section);
+                    // Place the function's literals after it
+                    let pool = Line::new(&line.file, line.number, ".pool".to_string());
+                    scope.source.push(Source::Line(pool));
+                    // Export the function's name to the enclosing scope
+                    let names: Vec<_> = scope
+                        .symbols
+                        .iter()
+                        .filter(|(_, &idx)| idx == 0)
+                        .map(|(name, _)| name.clone())
+                        .collect();
+                    for name in names {
+                        scope.symbols.remove(&name);
+                        if scope.globals.remove(&name) {
+                            self.globals.insert(name.clone());
+                        }
+                        self.symbols.insert(name, self.source.len());
+                    }
                     self.source.push(Source::Scope(scope));
                 }
                 [".", "end"] => return,
+                [".", "entry"] => {
+                    self.symbols.insert(ENTRY.to_string(), self.source.len());
+                    self.globals.insert(ENTRY.to_string());
+                }
+                [".", "global", symbol] => {
+                    self.globals.insert(symbol.to_string());
+                }
+                [".", name @ ("text" | "data")] => *section = name.to_string(),
                 [symbol, ":"] => {
-                    self.symbols
-                        .insert(symbol.to_string(), self.source.len());
+                    self.symbols.insert(symbol.to_string(), self.source.len());
+                }
+                _ => {
+                    line.section = section.clone();
+                    self.source.push(Source::Line(line));
                 }
-                _ => self.source.push(Source::Line(line)),
             }
         }
     }
 
-    pub fn subst(&mut self) {
-        // Perform substitution in 2 passes (descending the scope tree):
-        // 1. update symbol addresses
+    /// Resolves symbol references, returning every label defined.
+    ///
+    /// Each label's index refers to the line it names in the flattened scope.
+    /// Lines record which of their tokens refer to which label.
+    pub fn resolve(&mut self) -> Vec<Label> {
+        // Perform resolution in 2 passes (descending the scope tree):
+        // 1. update symbol indices
         self.update(&mut 0);
-        // 2. replace symbol occurences
-        self.replace(&mut 0, &HashMap::new());
+        // 2. record symbol references
+        let mut labels = Vec::new();
+        self.replace(&HashMap::new(), &mut labels);
+        labels
     }
 
     pub fn flatten(self) -> Vec<Line> {
@@ -65,40 +114,44 @@ impl Scope {
     }
 
     fn update(&mut self, count: &mut usize) {
-        self.symbols.iter_mut().for_each(|(_, v)| *v += *count);
+        // Map each source to the index of its first line
+        let mut idxs = Vec::with_capacity(self.source.len() + 1);
         for src in self.source.iter_mut() {
+            idxs.push(*count);
             match src {
                 Source::Line(_) => *count += 1,
-                Source::Scope(scope) => {
-                    scope.update(count);
-                    for (_, v) in self.symbols.iter_mut() {
-                        if *v > *count {
-                            *v += *count;
-                        }
-                    }
-                }
+                Source::Scope(scope) => scope.update(count),
             }
         }
+        idxs.push(*count);
+        self.symbols.values_mut().for_each(|v| *v = idxs[*v]);
     }
 
-    fn replace(&mut self, idx: &mut usize, symbols: &HashMap<String, usize>) {
-        self.symbols.extend(symbols.clone());
+    fn replace(&mut self, symbols: &HashMap<String, usize>, labels: &mut Vec<Label>) {
+        // Inner symbols shadow outer ones
+        let mut symbols = symbols.clone();
+        let mut inner: Vec<_> = self.symbols.iter().collect();
+        inner.sort_by_key(|&(name, &index)| (index, name));
+        for (name, &index) in inner {
+            symbols.insert(name.clone(), labels.len());
+            labels.push(Label {
+                name: name.clone(),
+                index,
+                global: self.globals.contains(name),
+            });
+        }
         for src in self.source.iter_mut() {
             match src {
                 Source::Line(line) => {
-                    for token in line
+                    line.refs = line
                         .tokens
-                        .iter_mut()
-                        .filter(|t| self.symbols.contains_key(*t))
-                    {
-                        let symbol = self.symbols[token] as iarch;
-                        let delta = symbol - (*idx as iarch + 1);
-                        let delta = (WORDSIZE as iarch).saturating_mul(delta);
-                        mem::swap(token, &mut format!("{:#x}", delta));
-                    }
-                    *idx += 1;
+                        .iter()
+                        .enumerate()
+                        .skip(1)
+                        .filter_map(|(idx, token)| Some((idx, *symbols.get(token)?)))
+                        .collect();
                 }
-                Source::Scope(scope) => scope.replace(idx, &self.symbols),
+                Source::Scope(scope) => scope.replace(&symbols, labels),
             }
         }
     }
@@ -1,69 +1,332 @@
-use std::collections::HashSet;
+use std::collections::HashMap;
 use std::error::Error;
 use std::fmt::{self, Display};
-use std::path::PathBuf;
+
+use isa::{reg, Encoding};
 
 use crate::line::Line;
-use crate::scope::Scope;
-use crate::{inst, uarch, VerboseError};
+use crate::obj::{Binding, Kind, Object, Reloc, Symbol, TEXT};
+use crate::scope::{Label, Scope};
+use crate::{iarch, inst, lex, uarch, VerboseError, WORDSIZE};
+
+/// Words a literal may lie beyond the line loading it.
+const REACH: usize = 0x80 / WORDSIZE;
 
 #[derive(Clone, Debug, Default)]
 pub struct Unit {
-    path: PathBuf,
     global: Scope,
 }
 
+/// A literal awaiting placement in a pool.
+#[derive(Debug)]
+struct Literal {
+    /// Line defining the literal.
+    word: Line,
+    label: usize,
+    /// Offset of the first line loading the literal, in words.
+    offset: usize,
+}
+
 impl Unit {
-    pub fn new(path: PathBuf, lines: Vec<Line>) -> Self {
+    pub fn new(lines: Vec<Line>) -> Self {
         Self {
-            path,
             global: Scope::new(lines),
         }
     }
 
-    pub fn concat(mut self, mut other: Unit) -> Result<Self, UnitError> {
-        // Ensure no duplicate symbols
-        let set1: HashSet<String> = self.global.symbols.keys().cloned().collect();
-        let set2: HashSet<String> = other.global.symbols.keys().cloned().collect();
-        let dups: Vec<_> = set1.intersection(&set2).cloned().collect();
-        if !dups.is_empty() {
-            return Err(UnitError::DuplicateSymbols(dups));
-        }
-        // Increment indices in other unit's symbols
-        other
-            .global
-            .symbols
-            .iter_mut()
-            .for_each(|(_, idx)| *idx += self.global.source.len());
-        // Concatenate translation units
-        self.global.source.extend(other.global.source);
-        self.global.symbols.extend(other.global.symbols);
-        // Return combined unit
-        Ok(self)
-    }
-
-    pub fn asm(mut self) -> Result<Vec<uarch>, VerboseError> {
-        // Perform symbol substitutions
-        self.global.subst();
+    pub fn asm(mut self, enc: Encoding) -> Result<Object, VerboseError> {
+        // Resolve symbol references
+        let mut labels = self.global.resolve();
         // Flatten the global scope
         let lines = self.global.flatten();
-        // Assemble instructions
-        lines
-            .into_iter()
+        // Place literals into pools
+        let lines = Self::pool(lines, &mut labels);
+        // Lay out lines within their sections
+        let mut obj = Object {
+            enc,
+            ..Default::default()
+        };
+        let layout: Vec<_> = lines
+            .iter()
             .map(|line| {
-                inst::asm(&line.tokens).map_err(|err| VerboseError {
+                let sec = obj.section(&line.section);
+                let words = &mut obj.sections[sec].words;
+                words.push(Default::default());
+                (sec, (words.len() - 1) * WORDSIZE)
+            })
+            .collect();
+        // Define labels at the line they name
+        let end = match layout.last() {
+            Some(&(sec, off)) => (sec, off + WORDSIZE),
+            None => (obj.section(TEXT), 0),
+        };
+        obj.symbols = labels
+            .into_iter()
+            .map(|label| Symbol {
+                name: label.name,
+                binding: match label.global {
+                    true => Binding::Global,
+                    false => Binding::Local,
+                },
+                def: Some(layout.get(label.index).copied().unwrap_or(end)),
+            })
+            .collect();
+        // Assemble lines
+        for (line, &(sec, off)) in lines.iter().zip(&layout) {
+            obj.sections[sec].words[off / WORDSIZE] = Self::line(&mut obj, line, (sec, off))
+                .map_err(|err| VerboseError {
                     err: From::from(err),
-                    loc: (self.path.clone(), line.number),
+                    loc: (line.file.clone(), line.number),
                     line: line.text.clone(),
-                })
+                })?;
+        }
+        Ok(obj)
+    }
+
+    /// Moves literals into pools, replacing `=value` operands with a label.
+    ///
+    /// Pending literals are placed at the next `.pool` directive, which the
+    /// assembler also inserts at the end of every function and the file.
+    /// Literals which would otherwise fall out of reach are placed after the
+    /// last line within reach which never continues to the next, or else after
+    /// a `goto` which skips over them.
+    fn pool(lines: Vec<Line>, labels: &mut Vec<Label>) -> Vec<Line> {
+        let mut out = Vec::with_capacity(lines.len());
+        let mut idxs = Vec::with_capacity(lines.len() + 1);
+        let mut offs: HashMap<String, usize> = HashMap::new();
+        let mut pending: Vec<Literal> = Vec::new();
+        // Where a pool could follow a line which never continues, as an index
+        // into the output and an offset within its section
+        let mut barrier: Option<(usize, usize)> = None;
+        let mut cond = false;
+        let nlabels = labels.len();
+        for mut line in lines {
+            let text = line.tokens.clone();
+            let tokens: Vec<_> = text.iter().map(String::as_str).collect();
+            let off = offs.get(&line.section).copied().unwrap_or_default();
+            // Ensure the oldest literal in this section stays within reach
+            let oldest = |pending: &[Literal]| {
+                pending
+                    .iter()
+                    .find(|lit| lit.word.section == line.section)
+                    .map(|lit| lit.offset + REACH)
+            };
+            if tokens[..] != [".", "pool"] {
+                if let (Some(limit), Some((idx, at))) = (oldest(&pending), barrier) {
+                    if off == limit {
+                        // Place literals loaded before the barrier after it
+                        let (before, after) = pending
+                            .drain(..)
+                            .partition(|lit| lit.word.section == line.section && lit.offset < at);
+                        pending = after;
+                        let pool = Self::place(before, idx, labels);
+                        let len = pool.len();
+                        out.splice(idx..idx, pool);
+                        // Shift the lines after the pool
+                        idxs.iter_mut()
+                            .filter(|i| **i >= idx)
+                            .for_each(|i| *i += len);
+                        pending
+                            .iter_mut()
+                            .filter(|lit| lit.word.section == line.section)
+                            .for_each(|lit| lit.offset += len);
+                        *offs.entry(line.section.clone()).or_default() += len;
+                        barrier = None;
+                    }
+                }
+                let off = offs.get(&line.section).copied().unwrap_or_default();
+                let last = !Self::continues(&tokens) && !cond;
+                // Never separate a condition from the line it guards
+                let guard = tokens.first().is_some_and(|op| op.starts_with("if"));
+                let due = oldest(&pending)
+                    .is_some_and(|limit| limit == off + 1 || guard && limit == off + 2);
+                if due && barrier.is_none() && !last {
+                    // Skip over literals placed here
+                    let label = labels.len();
+                    labels.push(Label {
+                        name: ".pool".to_string(),
+                        index: Default::default(),
+                        global: false,
+                    });
+                    let mut skip = line.clone();
+                    skip.tokens = vec!["goto".to_string(), ".pool".to_string()];
+                    skip.refs = vec![(1, label)];
+                    out.push(skip);
+                    let (here, rest) = pending
+                        .drain(..)
+                        .partition(|lit| lit.word.section == line.section);
+                    pending = rest;
+                    let pool = Self::place(here, out.len(), labels);
+                    *offs.entry(line.section.clone()).or_default() += 1 + pool.len();
+                    out.extend(pool);
+                    labels[label].index = out.len();
+                }
+            }
+            idxs.push(out.len());
+            match &tokens[..] {
+                [".", "pool"] => {
+                    // Place pending literals here
+                    for lit in pending.drain(..) {
+                        *offs.entry(lit.word.section.clone()).or_default() += 1;
+                        labels[lit.label].index = out.len();
+                        out.push(lit.word);
+                    }
+                    barrier = None;
+                    continue;
+                }
+                ["ldr", _, ",", "=", value] => {
+                    let value = value.to_string();
+                    let target = line.refs.iter().find(|&&(idx, _)| idx == 4).copied();
+                    // Reuse an identical literal if possible, unless it will
+                    // be placed before this line
+                    let label = match pending.iter().find(|lit| {
+                        lit.word.section == line.section
+                            && lit.word.tokens[2] == value
+                            && lit.word.refs.first().map(|&(_, label)| label)
+                                == target.map(|(_, label)| label)
+                            && barrier.is_none_or(|(_, at)| lit.offset >= at)
+                    }) {
+                        Some(lit) => lit.label,
+                        None => {
+                            let label = labels.len();
+                            labels.push(Label {
+                                name: format!("={}", value),
+                                index: Default::default(),
+                                global: false,
+                            });
+                            let mut word = line.clone();
+                            word.tokens = vec![".".to_string(), "word".to_string(), value];
+                            word.refs = target.map(|(_, label)| (2, label)).into_iter().collect();
+                            pending.push(Literal {
+                                word,
+                                label,
+                                offset: off,
+                            });
+                            label
+                        }
+                    };
+                    // Load the literal from its pool
+                    line.tokens.truncate(3);
+                    line.tokens.push(labels[label].name.clone());
+                    line.refs = vec![(3, label)];
+                }
+                _ => (),
+            }
+            // Note where a pool could follow this line
+            let off = offs.entry(line.section.clone()).or_default();
+            *off += 1;
+            let last = !Self::continues(&tokens) && !cond;
+            if last && pending.first().map(|lit| &lit.word.section) == Some(&line.section) {
+                barrier = Some((out.len() + 1, *off));
+            }
+            cond = tokens.first().is_some_and(|op| op.starts_with("if"));
+            out.push(line);
+        }
+        idxs.push(out.len());
+        // Update labels to account for the moved lines
+        for label in &mut labels[..nlabels] {
+            label.index = idxs[label.index];
+        }
+        out
+    }
+
+    /// Places literals in a pool starting at an index, returning its lines.
+    fn place(lits: Vec<Literal>, idx: usize, labels: &mut [Label]) -> Vec<Line> {
+        lits.into_iter()
+            .enumerate()
+            .map(|(i, lit)| {
+                labels[lit.label].index = idx + i;
+                lit.word
             })
             .collect()
     }
+
+    /// Checks whether execution may continue past a line to the next.
+    fn continues(tokens: &[&str]) -> bool {
+        let pc = |reg: &str| lex::parse_reg(reg).ok() == reg::lookup("pc");
+        match tokens {
+            ["goto" | "b" | "bal", ..] | ["rti"] => false,
+            ["mov" | "ldr", reg, ..] | ["pop", reg] => !pc(reg),
+            _ => true,
+        }
+    }
+
+    fn line(obj: &mut Object, line: &Line, loc: (usize, usize)) -> Result<uarch, Box<dyn Error>> {
+        let reloc = |obj: &mut Object, kind, symbol| {
+            obj.relocs.push(Reloc {
+                section: loc.0,
+                offset: loc.1,
+                kind,
+                symbol,
+            })
+        };
+        // Assemble data
+        if let [".", "word", value] =
+            &line.tokens.iter().map(String::as_str).collect::<Vec<_>>()[..]
+        {
+            let symbol = match line.refs.first() {
+                Some(&(_, label)) => label,
+                None if lex::is_symbol(value) => Self::import(obj, value),
+                None => return Ok(lex::parse_imm(value)?),
+            };
+            reloc(obj, Kind::Abs, symbol);
+            return Ok(Default::default());
+        }
+        // Substitute labels with their displacement
+        let mut tokens = line.tokens.clone();
+        for &(idx, label) in &line.refs {
+            let (sec, off) = obj.symbols[label].def.unwrap();
+            tokens[idx] = match sec == loc.0 {
+                true => {
+                    let delta = off as iarch - (loc.1 + WORDSIZE) as iarch;
+                    // Ensure literals are within reach
+                    let literal = obj.symbols[label].name.starts_with('=');
+                    if literal && !(-0x80..0x80).contains(&delta) {
+                        return Err(UnitError::PoolOutOfRange.into());
+                    }
+                    format!("{:#x}", delta)
+                }
+                false => {
+                    reloc(obj, Kind::Pc, label);
+                    format!("{:#x}", 0)
+                }
+            };
+        }
+        // Substitute undefined symbols for the linker to resolve
+        for token in tokens.iter_mut().skip(1) {
+            if lex::is_symbol(token) {
+                let symbol = Self::import(obj, token);
+                reloc(obj, Kind::Pc, symbol);
+                *token = format!("{:#x}", 0);
+            }
+        }
+        // Assemble instruction
+        inst::asm(&tokens, obj.enc)
+    }
+
+    /// Finds or declares an undefined symbol.
+    fn import(obj: &mut Object, name: &str) -> usize {
+        match obj
+            .symbols
+            .iter()
+            .position(|sym| sym.def.is_none() && sym.name == name)
+        {
+            Some(idx) => idx,
+            None => {
+                obj.symbols.push(Symbol {
+                    name: name.to_string(),
+                    binding: Binding::Global,
+                    def: None,
+                });
+                obj.symbols.len() - 1
+            }
+        }
+    }
 }
 
 #[derive(Debug)]
 pub enum UnitError {
-    DuplicateSymbols(Vec<String>),
+    PoolOutOfRange,
 }
 
 impl Display for UnitError {
@@ -72,10 +335,60 @@ impl Display for UnitError {
             f,
             "{}",
             match self {
-                Self::DuplicateSymbols(dups) => format!("Duplicate symbols: {:?}", dups),
+                Self::PoolOutOfRange => "Literal pool out of range; place a `.pool` nearby",
             }
         )
     }
 }
 
 impl Error for UnitError {}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn asm(src: &str) -> Result<Object, VerboseError> {
+        let lines = src
+            .lines()
+            .enumerate()
+            .map(|(idx, line)| Line::new(Path::new("test.s"), idx + 1, line.to_string()))
+            .filter(|line| !line.tokens.is_empty())
+            .collect();
+        Unit::new(lines).asm(Encoding::V1)
+    }
+
+    #[test]
+    fn pool() {
+        let obj = asm("ldr r0, =0x1234\nldr r1, =0x1234\nhlt").unwrap();
+        assert_eq!(obj.sections[0].words, vec![0x3082, 0x3181, 0x0c00, 0x1234]);
+        // Ensure distant pools are skipped over
+        let src = format!("ldr r0, =0x1234\n{}", "hlt\n".repeat(0x40));
+        let words = &asm(&src).unwrap().sections[0].words;
+        assert_eq!(words.len(), 0x43);
+        assert_eq!(words[0x00], 0x30bf);
+        assert_eq!(words[0x3f..0x41], [0x0081, 0x1234]);
+        // Ensure conditions stay with the line they guard
+        let src = format!(
+            "ldr r0, =0x1234\n{}ifeq\nmov r1, 0x1\nhlt",
+            "mov r2, r2\n".repeat(0x3d),
+        );
+        let words = &asm(&src).unwrap().sections[0].words;
+        assert_eq!(words.len(), 0x43);
+        assert_eq!(words[0x00], 0x30be);
+        assert_eq!(words[0x3e..0x42], [0x0081, 0x1234, 0x0e02, 0x7181]);
+        // Ensure pools follow the last branch within reach
+        let src = format!(
+            ".func\n_f:\nldr r0, =0x1234\n{}goto _f\n_g:\n{}goto _g\n.end",
+            "hlt\n".repeat(0x10),
+            "hlt\n".repeat(0x30),
+        );
+        let obj = asm(&src).unwrap();
+        let words = &obj.sections[0].words;
+        assert_eq!(words.len(), 0x44);
+        assert_eq!(words[0x00], 0x3091);
+        assert_eq!(words[0x11..0x13], [0x00ee, 0x1234]);
+        assert_eq!(words[0x43], 0x00cf);
+    }
+}
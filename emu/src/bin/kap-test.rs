@@ -0,0 +1,87 @@
+use std::path::PathBuf;
+use std::process;
+
+use clap::{Parser, ValueHint};
+use emu::harness::Suite;
+use env_logger as logger;
+use log::error;
+
+fn main() {
+    // Initialize logger
+    logger::Builder::new()
+        .default_format()
+        .format_indent(Some(12))
+        .format_timestamp(None)
+        .parse_default_env()
+        .init();
+    // Parse opts
+    let args = Args::parse();
+
+    // Run each suite's cases
+    let (mut passed, mut failed) = (0, 0);
+    for path in &args.suites {
+        let suite = Suite::load(path).unwrap_or_else(|err| {
+            error!("`{}`: {}", path.display(), err);
+            process::exit(1)
+        });
+        let cases = suite
+            .cases
+            .iter()
+            .filter(|case| args.filter.iter().all(|name| case.name.contains(name)));
+        for case in cases {
+            match case.run() {
+                Ok(outcome) if outcome.passed() => {
+                    println!("test {} ... ok", case.name);
+                    passed += 1;
+                }
+                Ok(outcome) => {
+                    println!("test {} ... FAILED", case.name);
+                    for diff in &outcome.diffs {
+                        println!("    {}", diff);
+                    }
+                    println!("  Final state:");
+                    for line in outcome.state.lines() {
+                        println!("    {}", line);
+                    }
+                    failed += 1;
+                }
+                Err(err) => {
+                    println!("test {} ... ERROR", case.name);
+                    println!("    {}", err);
+                    failed += 1;
+                }
+            }
+        }
+    }
+    // Report the results
+    println!();
+    println!(
+        "test result: {}. {} passed; {} failed",
+        if failed == 0 { "ok" } else { "FAILED" },
+        passed,
+        failed
+    );
+    if failed != 0 {
+        process::exit(1);
+    }
+}
+
+/// Test runner for KAP-16 programs.
+#[derive(Parser, Debug)]
+#[clap(author, version, about)]
+struct Args {
+    /// Test suite files
+    #[clap(parse(from_os_str))]
+    #[clap(min_values = 1)]
+    #[clap(value_hint = ValueHint::FilePath)]
+    suites: Vec<PathBuf>,
+
+    /// Only run cases whose name contains this
+    #[clap(long)]
+    filter: Option<String>,
+
+    /// Use verbose output (-v, -vv, -vvv, etc.)
+    #[clap(short, long)]
+    #[clap(parse(from_occurrences))]
+    verbose: u8,
+}
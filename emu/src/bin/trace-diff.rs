@@ -0,0 +1,107 @@
+use std::error::Error;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use clap::{Parser, ValueHint};
+use emu::trace::{self, Format, Record};
+use env_logger as logger;
+use log::error;
+
+fn main() {
+    // Initialize logger
+    logger::Builder::new()
+        .default_format()
+        .format_indent(Some(12))
+        .format_timestamp(None)
+        .parse_default_env()
+        .init();
+    // Parse opts
+    let args = Args::parse();
+
+    // Open both traces
+    let mut left = open(&args.left);
+    let mut right = open(&args.right);
+    // Compare them record by record
+    for idx in 0.. {
+        let (lrec, rrec) = match (next(&mut left, &args.left), next(&mut right, &args.right)) {
+            (None, None) => {
+                println!("Traces are identical ({} records)", idx);
+                process::exit(0);
+            }
+            (Some(rec), None) => ended(idx, &args.right, &args.left, &rec),
+            (None, Some(rec)) => ended(idx, &args.left, &args.right, &rec),
+            (Some(lrec), Some(rrec)) => (lrec, rrec),
+        };
+        let fields: Vec<_> = lrec
+            .diff(&rrec)
+            .into_iter()
+            .filter(|field| !args.ignore.iter().any(|other| other == field))
+            .collect();
+        if !fields.is_empty() {
+            println!("Traces diverge at record {} in {}", idx, fields.join(", "));
+            println!("  {}: {}", args.left.display(), lrec.to_json());
+            println!("  {}: {}", args.right.display(), rrec.to_json());
+            process::exit(1);
+        }
+    }
+}
+
+/// Reports that one trace ended before the other, then exits.
+fn ended(idx: usize, path: &Path, other: &Path, rec: &Record) -> ! {
+    println!(
+        "Traces diverge at record {}: `{}` ends before `{}`",
+        idx,
+        path.display(),
+        other.display()
+    );
+    println!("  {}: {}", other.display(), rec.to_json());
+    process::exit(1)
+}
+
+type Records = Box<dyn Iterator<Item = Result<Record, Box<dyn Error>>>>;
+
+/// Opens a trace, exiting upon failure.
+fn open(path: &Path) -> Records {
+    Format::from_path(path)
+        .map_err(Into::into)
+        .and_then(|format| {
+            let file = BufReader::new(File::open(path)?);
+            Ok(Box::new(trace::read(file, format)) as Records)
+        })
+        .unwrap_or_else(|err: Box<dyn Error>| {
+            error!("`{}`: {}", path.display(), err);
+            process::exit(2)
+        })
+}
+
+/// Reads the next record of a trace, exiting upon failure.
+fn next(records: &mut Records, path: &Path) -> Option<Record> {
+    records.next().map(|rec| {
+        rec.unwrap_or_else(|err| {
+            error!("`{}`: {}", path.display(), err);
+            process::exit(2)
+        })
+    })
+}
+
+/// Finds where two execution traces first diverge.
+#[derive(Parser, Debug)]
+#[clap(author, version, about)]
+struct Args {
+    /// First trace, as a `.jsonl` or `.csv` file
+    #[clap(parse(from_os_str))]
+    #[clap(value_hint = ValueHint::FilePath)]
+    left: PathBuf,
+
+    /// Second trace, as a `.jsonl` or `.csv` file
+    #[clap(parse(from_os_str))]
+    #[clap(value_hint = ValueHint::FilePath)]
+    right: PathBuf,
+
+    /// Ignore differences in a field, such as `cycle`
+    #[clap(long)]
+    #[clap(value_name = "FIELD")]
+    ignore: Vec<String>,
+}
@@ -0,0 +1,367 @@
+//! Memory bus.
+//!
+//! The processor accesses memory a word at a time through the [`Bus`] trait.
+//! Its address space is described by a [`Map`], which is split into regions:
+//! - RAM, starting at address `0x0000`.
+//! - ROM, a write-protected prefix of RAM holding the program image.
+//! - The system control block, at the top of the address space.
+//! - Devices, each attached to its own range of addresses.
+//!
+//! Devices are advanced once per cycle, and may request an interrupt on the
+//! line numbered by the order in which they were attached.
+
+use std::error::Error;
+use std::fmt::{self, Debug, Display};
+use std::ops::Range;
+
+use crate::ctl::{self, Ctl};
+use crate::ram::Ram;
+use crate::{uarch, RAMSIZE, WORDSIZE};
+
+/// Anything which can be accessed over the bus.
+///
+/// Addresses are relative to the start of the region being accessed.
+/// Accesses that fail cause a bus error.
+pub trait Bus {
+    /// Reads a word, possibly with side effects.
+    fn read(&mut self, addr: uarch) -> Option<uarch>;
+
+    /// Writes a word.
+    fn write(&mut self, addr: uarch, word: uarch) -> Option<()>;
+
+    /// Reads a word without side effects, for inspection by debuggers.
+    fn peek(&self, _addr: uarch) -> Option<uarch> {
+        None
+    }
+
+    /// Advances by a cycle, returning whether to request an interrupt.
+    fn tick(&mut self) -> bool {
+        false
+    }
+
+    /// Checks whether a tick would change internal state in a way that
+    /// [`Bus::untick`] cannot reverse.
+    fn busy(&self) -> bool {
+        false
+    }
+
+    /// Reverses ticks taken while not [busy](Bus::busy).
+    fn untick(&mut self, _ticks: u64) {}
+
+    /// Saves internal state, to be included in snapshots.
+    fn save(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    /// Restores internal state saved by [`Bus::save`].
+    ///
+    /// Returns `None` if the state is invalid.
+    fn restore(&mut self, state: &[u8]) -> Option<()> {
+        state.is_empty().then_some(())
+    }
+}
+
+/// Serializes words, for devices whose state is a set of registers.
+pub fn save_words(words: &[uarch]) -> Vec<u8> {
+    words.iter().flat_map(|word| word.to_le_bytes()).collect()
+}
+
+/// Deserializes exactly `N` words saved by [`save_words`].
+pub fn restore_words<const N: usize>(state: &[u8]) -> Option<[uarch; N]> {
+    if state.len() != N * WORDSIZE {
+        return None;
+    }
+    let mut words = [0; N];
+    for (word, bytes) in words.iter_mut().zip(state.chunks(WORDSIZE)) {
+        *word = uarch::from_le_bytes([bytes[0], bytes[1]]);
+    }
+    Some(words)
+}
+
+impl Debug for dyn Bus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Bus")
+    }
+}
+
+impl<const N: usize> Bus for Ram<N> {
+    fn read(&mut self, addr: uarch) -> Option<uarch> {
+        self.peek(addr)
+    }
+
+    fn write(&mut self, addr: uarch, word: uarch) -> Option<()> {
+        let idx = addr as usize;
+        if !idx.is_multiple_of(WORDSIZE) {
+            return None;
+        }
+        *self.get_mut(idx / WORDSIZE)? = word;
+        Some(())
+    }
+
+    fn peek(&self, addr: uarch) -> Option<uarch> {
+        let idx = addr as usize;
+        if !idx.is_multiple_of(WORDSIZE) {
+            return None;
+        }
+        self.get(idx / WORDSIZE).copied()
+    }
+}
+
+#[derive(Debug)]
+struct Mapping {
+    range: Range<usize>,
+    dev: Box<dyn Bus>,
+}
+
+/// The processor's address space.
+#[derive(Debug, Default)]
+pub struct Map {
+    pub ram: Ram<RAMSIZE>,
+    pub ctl: Ctl,
+    /// Number of bytes at the start of RAM which are read-only.
+    rom: usize,
+    devices: Vec<Mapping>,
+}
+
+impl Map {
+    /// Write-protects the first `len` bytes of RAM.
+    pub fn protect(&mut self, len: usize) {
+        self.rom = len.min(RAMSIZE);
+    }
+
+    /// Number of bytes at the start of RAM which are read-only.
+    pub fn protected(&self) -> usize {
+        self.rom
+    }
+
+    /// Attaches a device at a range of addresses, returning its interrupt line.
+    pub fn attach(&mut self, range: Range<usize>, dev: Box<dyn Bus>) -> Result<usize, MapError> {
+        // Ensure the range is free
+        let overlaps = |other: &Range<usize>| range.start < other.end && other.start < range.end;
+        if range.is_empty()
+            || range.end > 1 << uarch::BITS
+            || overlaps(&(0..RAMSIZE))
+            || overlaps(&ctl::RANGE)
+            || self.devices.iter().any(|mapping| overlaps(&mapping.range))
+        {
+            return Err(MapError::Overlap(range));
+        }
+        self.devices.push(Mapping { range, dev });
+        Ok(self.devices.len() - 1)
+    }
+
+    /// Replaces the device attached at a range of addresses, keeping its
+    /// interrupt line.
+    ///
+    /// Returns the replaced device, or `None` if no device is attached at
+    /// exactly that range.
+    pub fn replace(&mut self, range: Range<usize>, dev: Box<dyn Bus>) -> Option<Box<dyn Bus>> {
+        let mapping = self
+            .devices
+            .iter_mut()
+            .find(|mapping| mapping.range == range)?;
+        Some(std::mem::replace(&mut mapping.dev, dev))
+    }
+
+    /// Advances every device by a cycle, recording their interrupt requests.
+    ///
+    /// Each part of the map is passed to `changing` before a tick changes its
+    /// state, unless [`Map::untick`] can reverse the change.
+    pub fn tick(&mut self, mut changing: impl FnMut(&Self, Part)) {
+        for line in 0..self.devices.len() {
+            if self.devices[line].dev.busy() {
+                changing(self, Part::Device(line));
+            }
+            if self.devices[line].dev.tick() {
+                if !self.ctl.requested(line) {
+                    changing(self, Part::Ctl);
+                }
+                self.ctl.request(line);
+            }
+        }
+    }
+
+    /// Reverses ticks taken by every device, given how many by their line.
+    pub fn untick(&mut self, ticks: impl Fn(usize) -> u64) {
+        for (line, mapping) in self.devices.iter_mut().enumerate() {
+            mapping.dev.untick(ticks(line));
+        }
+    }
+
+    /// Finds the part of the map an address belongs to, or `None` for RAM.
+    pub fn part(&self, addr: uarch) -> Option<Part> {
+        match Self::ctl(addr) {
+            Some(_) => Some(Part::Ctl),
+            None => self.device(addr).map(|(line, _)| Part::Device(line)),
+        }
+    }
+
+    /// Saves the state of the device on an interrupt line.
+    pub fn save_device(&self, line: usize) -> Vec<u8> {
+        self.devices[line].dev.save()
+    }
+
+    /// Restores the state of the device on an interrupt line.
+    ///
+    /// Returns `None` if no such device is attached, or the state is invalid.
+    pub fn restore_device(&mut self, line: usize, state: &[u8]) -> Option<()> {
+        self.devices.get_mut(line)?.dev.restore(state)
+    }
+
+    /// Saves the state of every device, in the order they were attached.
+    pub fn save_devices(&self) -> Vec<Vec<u8>> {
+        self.devices
+            .iter()
+            .map(|mapping| mapping.dev.save())
+            .collect()
+    }
+
+    /// Restores the state of every device.
+    ///
+    /// Returns `None` if the states do not match the attached devices.
+    pub fn restore_devices(&mut self, states: &[Vec<u8>]) -> Option<()> {
+        if states.len() != self.devices.len() {
+            return None;
+        }
+        for (mapping, state) in self.devices.iter_mut().zip(states) {
+            mapping.dev.restore(state)?;
+        }
+        Some(())
+    }
+
+    /// Finds the device mapped at an address, along with its offset.
+    fn device(&self, addr: uarch) -> Option<(usize, uarch)> {
+        let addr = addr as usize;
+        self.devices
+            .iter()
+            .position(|mapping| mapping.range.contains(&addr))
+            .map(|idx| (idx, (addr - self.devices[idx].range.start) as uarch))
+    }
+
+    /// Finds the offset of an address within the system control block.
+    fn ctl(addr: uarch) -> Option<uarch> {
+        ctl::RANGE
+            .contains(&(addr as usize))
+            .then(|| (addr as usize - ctl::RANGE.start) as uarch)
+    }
+}
+
+impl Bus for Map {
+    fn read(&mut self, addr: uarch) -> Option<uarch> {
+        if let Some(off) = Self::ctl(addr) {
+            return self.ctl.read(off);
+        }
+        match self.device(addr) {
+            Some((idx, off)) => self.devices[idx].dev.read(off),
+            None => self.ram.read(addr),
+        }
+    }
+
+    fn write(&mut self, addr: uarch, word: uarch) -> Option<()> {
+        if let Some(off) = Self::ctl(addr) {
+            return self.ctl.write(off, word);
+        }
+        match self.device(addr) {
+            Some((idx, off)) => self.devices[idx].dev.write(off, word),
+            None if (addr as usize) < self.rom => None,
+            None => self.ram.write(addr, word),
+        }
+    }
+
+    fn peek(&self, addr: uarch) -> Option<uarch> {
+        if let Some(off) = Self::ctl(addr) {
+            return self.ctl.peek(off);
+        }
+        match self.device(addr) {
+            Some((idx, off)) => self.devices[idx].dev.peek(off),
+            None => self.ram.peek(addr),
+        }
+    }
+}
+
+impl Display for Map {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.ram)
+    }
+}
+
+/// A part of the map whose state is saved separately from RAM.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Part {
+    /// The system control block.
+    Ctl,
+    /// The device on an interrupt line.
+    Device(usize),
+}
+
+#[derive(Debug)]
+pub enum MapError {
+    Overlap(Range<usize>),
+}
+
+impl Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Overlap(range) => format!(
+                    "Cannot map device at {:#06x}..{:#06x}; range is unavailable",
+                    range.start, range.end
+                ),
+            }
+        )
+    }
+}
+
+impl Error for MapError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Counts accesses to itself.
+    #[derive(Default)]
+    struct Counter(uarch);
+
+    impl Bus for Counter {
+        fn read(&mut self, _: uarch) -> Option<uarch> {
+            self.0 += 1;
+            Some(self.0)
+        }
+
+        fn write(&mut self, _: uarch, word: uarch) -> Option<()> {
+            self.0 = word;
+            Some(())
+        }
+    }
+
+    #[test]
+    fn map() {
+        let mut map = Map::default();
+        map.attach(0xff00..0xff02, Box::new(Counter::default()))
+            .unwrap();
+        // Ensure devices are routed to
+        assert_eq!(map.read(0xff00), Some(1));
+        assert_eq!(map.write(0xff00, 0x10), Some(()));
+        assert_eq!(map.read(0xff00), Some(0x11));
+        assert_eq!(map.peek(0xff00), None);
+        // Ensure unmapped, misaligned, and read-only accesses fail
+        assert_eq!(map.read(0x8000), None);
+        assert_eq!(map.read(0x0001), None);
+        map.protect(0x0004);
+        assert_eq!(map.write(0x0002, 0x1234), None);
+        assert_eq!(map.write(0x0004, 0x1234), Some(()));
+        assert_eq!(map.peek(0x0004), Some(0x1234));
+        // Ensure mappings cannot overlap
+        assert!(map
+            .attach(0x3ffe..0x4002, Box::new(Counter::default()))
+            .is_err());
+        assert!(map
+            .attach(0xff01..0xff03, Box::new(Counter::default()))
+            .is_err());
+        assert!(map
+            .attach(0xfff0..0xfff2, Box::new(Counter::default()))
+            .is_err());
+    }
+}
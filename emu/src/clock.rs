@@ -0,0 +1,115 @@
+//! Cycle counter.
+//!
+//! A read-only, 64-bit count of the cycles executed, split across four words
+//! with the least significant first:
+//!
+//! | Offset   | Name     | Description                      |
+//! | -------- | -------- | -------------------------------- |
+//! | `0x0000` | `CYCLE0` | Bits 0-15, latching the count    |
+//! | `0x0002` | `CYCLE1` | Bits 16-31 of the latched count  |
+//! | `0x0004` | `CYCLE2` | Bits 32-47 of the latched count  |
+//! | `0x0006` | `CYCLE3` | Bits 48-63 of the latched count  |
+//!
+//! Reading `CYCLE0` latches the full count, so that the remaining words are
+//! consistent with it even as the counter keeps running.
+
+use std::ops::Range;
+
+use crate::bus::{self, Bus};
+use crate::{uarch, WORDSIZE};
+
+/// Addresses the emulator maps the cycle counter at.
+pub const RANGE: Range<usize> = 0xffe8..0xfff0;
+
+#[derive(Debug, Default)]
+pub struct Clock {
+    count: u64,
+    latch: u64,
+}
+
+impl Clock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Extracts a word of a count.
+    fn word(count: u64, addr: uarch) -> Option<uarch> {
+        if !(addr as usize).is_multiple_of(WORDSIZE) || addr as usize >= RANGE.len() {
+            return None;
+        }
+        Some((count >> (uarch::BITS as usize * (addr as usize / WORDSIZE))) as uarch)
+    }
+}
+
+impl Bus for Clock {
+    fn read(&mut self, addr: uarch) -> Option<uarch> {
+        if addr == 0x0000 {
+            self.latch = self.count;
+        }
+        Self::word(self.latch, addr)
+    }
+
+    fn write(&mut self, _: uarch, _: uarch) -> Option<()> {
+        None
+    }
+
+    fn peek(&self, addr: uarch) -> Option<uarch> {
+        Self::word(self.count, addr)
+    }
+
+    fn tick(&mut self) -> bool {
+        self.count += 1;
+        false
+    }
+
+    fn untick(&mut self, ticks: u64) {
+        self.count -= ticks;
+    }
+
+    fn save(&self) -> Vec<u8> {
+        [self.count, self.latch]
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect()
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Option<()> {
+        let words: [uarch; 8] = bus::restore_words(state)?;
+        let value = |words: &[uarch]| {
+            words
+                .iter()
+                .rev()
+                .fold(0, |value, &word| value << uarch::BITS | word as u64)
+        };
+        self.count = value(&words[..4]);
+        self.latch = value(&words[4..]);
+        Some(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latch() {
+        let mut clock = Clock::new();
+        clock.count = 0x0001_fffe;
+        // Ensure reads are consistent as the count carries
+        assert_eq!(clock.read(0x0000), Some(0xfffe));
+        clock.tick();
+        clock.tick();
+        assert_eq!(clock.read(0x0002), Some(0x0001));
+        assert_eq!(clock.peek(0x0002), Some(0x0002));
+        assert_eq!(clock.read(0x0000), Some(0x0000));
+        assert_eq!(clock.read(0x0002), Some(0x0002));
+        // Ensure the counter is read-only
+        assert_eq!(clock.write(0x0000, 0x0000), None);
+        assert_eq!(clock.read(0x0008), None);
+        // Ensure state is restored
+        let mut other = Clock::new();
+        other.restore(&clock.save()).unwrap();
+        assert_eq!(other.peek(0x0002), Some(0x0002));
+        assert_eq!(other.read(0x0002), Some(0x0002));
+    }
+}
@@ -0,0 +1,130 @@
+//! System control block.
+//!
+//! A small set of registers mapped at the top of the address space, through
+//! which programs install handlers, inspect the faults they catch, and control
+//! interrupts:
+//!
+//! | Address  | Name    | Description                                   |
+//! | -------- | ------- | --------------------------------------------- |
+//! | `0xfff0` | `VBAR`  | Base address of the vector table (0 disables) |
+//! | `0xfff2` | `CAUSE` | Cause of the last fault                       |
+//! | `0xfff4` | `INFO`  | Faulting address or instruction word          |
+//! | `0xfff6` | `EPC`   | Address of the faulting instruction           |
+//! | `0xfff8` | `IPC`   | Address to return to from an interrupt        |
+//! | `0xfffa` | `ISR`   | Status register to restore on return          |
+//! | `0xfffc` | `IPEND` | Pending interrupt lines (write 1 to clear)    |
+//! | `0xfffe` | `IMASK` | Enabled interrupt lines                       |
+
+use std::ops::Range;
+
+use crate::bus::{self, Bus};
+use crate::stop::{Fault, FaultKind};
+use crate::{uarch, WORDSIZE};
+
+/// Addresses occupied by the system control block.
+pub const RANGE: Range<usize> = 0xfff0..0x10000;
+
+/// Offset of the fault handler's entry within the vector table.
+pub const FAULT: uarch = 0x0000;
+/// Offset of the interrupt handler's entry within the vector table.
+pub const IRQ: uarch = 0x0002;
+
+#[derive(Debug, Default)]
+pub struct Ctl {
+    pub vbar: uarch,
+    pub cause: uarch,
+    pub info: uarch,
+    pub epc: uarch,
+    pub ipc: uarch,
+    pub isr: uarch,
+    pub ipend: uarch,
+    pub imask: uarch,
+}
+
+impl Ctl {
+    /// Records a fault as it is handed to the fault handler.
+    pub fn raise(&mut self, fault: Fault) {
+        (self.cause, self.info) = match fault.kind {
+            FaultKind::Fetch => (0x1, fault.pc),
+            FaultKind::Undefined(word) => (0x2, word),
+            FaultKind::Bus(addr) => (0x3, addr),
+        };
+        self.epc = fault.pc;
+    }
+
+    /// Marks an interrupt line as pending.
+    pub fn request(&mut self, line: usize) {
+        if line < uarch::BITS as usize {
+            self.ipend |= 1 << line;
+        }
+    }
+
+    /// Checks if requesting an interrupt line would leave it unchanged.
+    pub fn requested(&self, line: usize) -> bool {
+        line >= uarch::BITS as usize || self.ipend & 1 << line != 0
+    }
+
+    /// Checks if any enabled interrupt line is pending.
+    pub fn pending(&self) -> bool {
+        self.ipend & self.imask != 0
+    }
+
+    /// Values of every register, in address order.
+    fn regs(&self) -> [uarch; 8] {
+        [
+            self.vbar, self.cause, self.info, self.epc, self.ipc, self.isr, self.ipend, self.imask,
+        ]
+    }
+
+    /// Finds the register at an offset within the block.
+    fn reg(&mut self, addr: uarch) -> Option<&mut uarch> {
+        if !(addr as usize).is_multiple_of(WORDSIZE) {
+            return None;
+        }
+        match addr as usize / WORDSIZE {
+            0 => Some(&mut self.vbar),
+            1 => Some(&mut self.cause),
+            2 => Some(&mut self.info),
+            3 => Some(&mut self.epc),
+            4 => Some(&mut self.ipc),
+            5 => Some(&mut self.isr),
+            6 => Some(&mut self.ipend),
+            7 => Some(&mut self.imask),
+            _ => None,
+        }
+    }
+}
+
+impl Bus for Ctl {
+    fn read(&mut self, addr: uarch) -> Option<uarch> {
+        self.peek(addr)
+    }
+
+    fn write(&mut self, addr: uarch, word: uarch) -> Option<()> {
+        let reg = self.reg(addr)?;
+        // Pending lines are cleared by writing ones to them
+        match addr {
+            0x000c => *reg &= !word,
+            _ => *reg = word,
+        }
+        Some(())
+    }
+
+    fn peek(&self, addr: uarch) -> Option<uarch> {
+        if !(addr as usize).is_multiple_of(WORDSIZE) {
+            return None;
+        }
+        self.regs().get(addr as usize / WORDSIZE).copied()
+    }
+
+    fn save(&self) -> Vec<u8> {
+        bus::save_words(&self.regs())
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Option<()> {
+        [
+            self.vbar, self.cause, self.info, self.epc, self.ipc, self.isr, self.ipend, self.imask,
+        ] = bus::restore_words(state)?;
+        Some(())
+    }
+}
@@ -0,0 +1,609 @@
+//! Interactive debugger.
+//!
+//! The debugger drives an [`Emulator`] one instruction at a time from a
+//! command prompt. Commands may be abbreviated as shown, and an empty line
+//! repeats the previous command.
+//!
+//! | Command               | Action                                       |
+//! | --------------------- | -------------------------------------------- |
+//! | `s[tep] [N]`          | execute `N` instructions (default 1)         |
+//! | `c[ontinue]`          | run until a breakpoint or halt               |
+//! | `rs[tep] [N]`         | undo `N` instructions (default 1)            |
+//! | `rc[ontinue]`         | run backwards until a breakpoint             |
+//! | `lastw[rite] LOC`     | show the last recorded write to `LOC`        |
+//! | `b[reak] [LOC]`       | set a breakpoint, or list breakpoints        |
+//! | `wa[tch] [LOC [LEN]]` | watch writes to `LEN` bytes (default 2), or  |
+//! |                       | list watchpoints                             |
+//! | `rw[atch] LOC [LEN]`  | watch reads from `LEN` bytes (default 2)     |
+//! | `aw[atch] LOC [LEN]`  | watch accesses to `LEN` bytes (default 2)    |
+//! | `d[elete] [ID]`       | delete a breakpoint or watchpoint, or all    |
+//! | `p[rint] [REG]`       | print a register, flag, or every register    |
+//! | `set REG VALUE`       | set a register, `sr`, or flag                |
+//! | `x LOC [N]`           | examine `N` words of memory (default 1)      |
+//! | `w[rite] LOC WORD...` | write consecutive words to memory            |
+//! | `l[ist] [N]`          | disassemble `N` words around the PC          |
+//! | `h[elp]`              | show available commands                      |
+//! | `q[uit]`              | exit the debugger                            |
+//!
+//! Reverse execution relies on the emulator recording its history; see
+//! [`Emulator::set_history`].
+//!
+//! Watchpoints stop execution once a load or store accesses their range, and
+//! share their numbering with breakpoints.
+//!
+//! Locations are addresses or the names of symbols loaded from a symbol map.
+//! Flags are named `i`, `c`, `v`, `n` and `z`.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::io::{self, BufRead, Read, Stdout, Write};
+
+use isa::{uarch, Instruction, WORDSIZE};
+
+use crate::sym::{parse_num, parse_reg, Symbols};
+use crate::{Emulator, Flag, StopReason, Watch, Watchpoint, RAMSIZE};
+
+const HELP: &str = "\
+step [N]          execute N instructions (default 1)
+continue          run until a breakpoint or halt
+rstep [N]         undo N instructions (default 1)
+rcontinue         run backwards until a breakpoint
+lastwrite LOC     show the last recorded write to LOC
+break [LOC]       set a breakpoint, or list breakpoints
+watch [LOC [LEN]] watch writes to LEN bytes (default 2), or list watchpoints
+rwatch LOC [LEN]  watch reads from LEN bytes (default 2)
+awatch LOC [LEN]  watch accesses to LEN bytes (default 2)
+delete [ID]       delete a breakpoint or watchpoint, or all
+print [REG]       print a register, flag, or every register
+set REG VALUE     set a register, `sr`, or flag
+x LOC [N]         examine N words of memory (default 1)
+write LOC WORD... write consecutive words to memory
+list [N]          disassemble N words around the PC
+help              show this message
+quit              exit the debugger";
+
+#[derive(Debug)]
+pub struct Debugger<R: BufRead, W: Write> {
+    input: R,
+    output: W,
+    symbols: Symbols,
+    breaks: BTreeMap<usize, uarch>,
+    watches: BTreeMap<usize, Watchpoint>,
+    next: usize,
+    last: String,
+}
+
+impl<R: BufRead, W: Write> Debugger<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Self {
+            input,
+            output,
+            symbols: Symbols::new(),
+            breaks: BTreeMap::new(),
+            watches: BTreeMap::new(),
+            next: 1,
+            last: String::new(),
+        }
+    }
+
+    /// Uses symbols to resolve and describe locations.
+    pub fn set_symbols(&mut self, symbols: Symbols) {
+        self.symbols = symbols;
+    }
+
+    /// Runs the command loop until quit or end of input.
+    pub fn run(&mut self, emu: &mut Emulator) -> io::Result<()> {
+        self.show(emu)?;
+        loop {
+            // Prompt for a command
+            write!(self.output, "(emu) ")?;
+            self.output.flush()?;
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                writeln!(self.output)?;
+                return Ok(());
+            }
+            // Repeat the last command on an empty line
+            let line = match line.trim() {
+                "" => self.last.clone(),
+                line => line.to_string(),
+            };
+            self.last = line.clone();
+            // Perform the command
+            match self.command(emu, &line) {
+                Ok(true) => return Ok(()),
+                Ok(false) => (),
+                Err(DebugError::Io(err)) => return Err(err),
+                Err(err) => writeln!(self.output, "{}", err)?,
+            }
+        }
+    }
+
+    /// Performs a command, returning whether to quit.
+    fn command(&mut self, emu: &mut Emulator, line: &str) -> Result<bool, DebugError> {
+        let args: Vec<_> = line.split_whitespace().collect();
+        match args[..] {
+            [] => (),
+            ["s" | "step"] => self.resume(emu, Some(1))?,
+            ["s" | "step", count] => {
+                let count = number(count)?;
+                self.resume(emu, Some(count as usize))?;
+            }
+            ["c" | "continue"] => self.resume(emu, None)?,
+            ["rs" | "rstep"] => self.reverse(emu, Some(1))?,
+            ["rs" | "rstep", count] => {
+                let count = number(count)?;
+                self.reverse(emu, Some(count as usize))?;
+            }
+            ["rc" | "rcontinue"] => self.reverse(emu, None)?,
+            ["lastw" | "lastwrite", loc] => {
+                let addr = self.locate(loc)?;
+                match emu.last_write(addr) {
+                    Some(write) => writeln!(
+                        self.output,
+                        "{:#06x} written by {} at cycle {}: {:#06x} -> {:#06x}",
+                        addr,
+                        self.symbols.symbolize(write.pc),
+                        write.cycle,
+                        write.old,
+                        write.new
+                    )?,
+                    None => writeln!(self.output, "No recorded write to {:#06x}", addr)?,
+                }
+            }
+            ["b" | "break"] => {
+                for (id, &addr) in &self.breaks {
+                    writeln!(self.output, "{}: {}", id, self.symbols.symbolize(addr))?;
+                }
+            }
+            ["b" | "break", loc] => {
+                let addr = self.locate(loc)?;
+                self.breaks.insert(self.next, addr);
+                emu.set_breakpoint(addr);
+                writeln!(
+                    self.output,
+                    "Breakpoint {} at {}",
+                    self.next,
+                    self.symbols.symbolize(addr)
+                )?;
+                self.next += 1;
+            }
+            ["wa" | "watch"] => {
+                for (id, wp) in &self.watches {
+                    writeln!(self.output, "{}: {}", id, self.describe(wp))?;
+                }
+            }
+            [cmd @ ("wa" | "watch" | "rw" | "rwatch" | "aw" | "awatch"), loc, ref len @ ..]
+                if len.len() <= 1 =>
+            {
+                let kind = match cmd {
+                    "wa" | "watch" => Watch::Write,
+                    "rw" | "rwatch" => Watch::Read,
+                    _ => Watch::Access,
+                };
+                let addr = self.locate(loc)? as usize;
+                let len = match len {
+                    [len] => number(len)? as usize,
+                    _ => WORDSIZE,
+                };
+                let wp = Watchpoint {
+                    range: addr..addr + len,
+                    kind,
+                };
+                emu.set_watchpoint(wp.range.clone(), kind);
+                writeln!(
+                    self.output,
+                    "Watchpoint {} on {}",
+                    self.next,
+                    self.describe(&wp)
+                )?;
+                self.watches.insert(self.next, wp);
+                self.next += 1;
+            }
+            ["d" | "delete"] => {
+                for (_, addr) in std::mem::take(&mut self.breaks) {
+                    emu.clear_breakpoint(addr);
+                }
+                for (_, wp) in std::mem::take(&mut self.watches) {
+                    emu.clear_watchpoint(wp.range, wp.kind);
+                }
+            }
+            ["d" | "delete", id] => {
+                let id = number(id)? as usize;
+                match (self.breaks.remove(&id), self.watches.remove(&id)) {
+                    (Some(addr), _) => {
+                        // Keep breakpoints others still refer to
+                        if !self.breaks.values().any(|&other| other == addr) {
+                            emu.clear_breakpoint(addr);
+                        }
+                    }
+                    (_, Some(wp)) => drop(emu.clear_watchpoint(wp.range, wp.kind)),
+                    (None, None) => return Err(DebugError::NoBreakpoint(id)),
+                }
+            }
+            ["p" | "print"] => writeln!(self.output, "{}", emu)?,
+            ["p" | "print", "sr"] => {
+                let flags: Vec<_> = Flag::ALL
+                    .into_iter()
+                    .filter(|&flag| emu.flag(flag))
+                    .map(Flag::name)
+                    .collect();
+                writeln!(self.output, "sr = {:#06x} [{}]", emu.sr(), flags.join(" "))?;
+            }
+            ["p" | "print", name] => match Flag::lookup(name) {
+                Some(flag) => writeln!(self.output, "{} = {}", name, emu.flag(flag) as u8)?,
+                None => {
+                    let reg = register(name)?;
+                    let value = emu.reg(reg);
+                    writeln!(self.output, "{} = {:#06x} ({})", name, value, value)?;
+                }
+            },
+            ["set", name, value] => {
+                let value = number(value)?;
+                match Flag::lookup(name) {
+                    Some(flag) => emu.set_flag(flag, value != 0),
+                    None if name == "sr" => emu.set_sr(value),
+                    None => emu.set_reg(register(name)?, value),
+                }
+            }
+            ["x", loc] => self.examine(emu, loc, 1)?,
+            ["x", loc, count] => {
+                let count = number(count)?;
+                self.examine(emu, loc, count as usize)?;
+            }
+            ["w" | "write", loc, ref words @ ..] if !words.is_empty() => {
+                let addr = self.locate(loc)?;
+                for (idx, word) in words.iter().enumerate() {
+                    let word = number(word)?;
+                    let addr = addr.wrapping_add((idx * WORDSIZE) as uarch);
+                    emu.write(addr, word).ok_or(DebugError::BadAddress(addr))?;
+                }
+            }
+            ["l" | "list"] => self.list(emu, 4)?,
+            ["l" | "list", count] => {
+                let count = number(count)?;
+                self.list(emu, count as usize)?;
+            }
+            ["h" | "help"] => writeln!(self.output, "{}", HELP)?,
+            ["q" | "quit"] => return Ok(true),
+            [cmd, ..] => return Err(DebugError::BadCommand(cmd.to_string())),
+        }
+        Ok(false)
+    }
+
+    /// Resumes execution for a number of instructions, or until stopped.
+    fn resume(&mut self, emu: &mut Emulator, count: Option<usize>) -> Result<(), DebugError> {
+        let reason = match count {
+            Some(0) => None,
+            Some(count) => {
+                let mut steps = 0;
+                Some(emu.run_until(|_| {
+                    steps += 1;
+                    steps == count
+                }))
+            }
+            None => Some(emu.run()),
+        };
+        self.report(reason)?;
+        self.show(emu)?;
+        Ok(())
+    }
+
+    /// Undoes a number of instructions, or until stopped.
+    fn reverse(&mut self, emu: &mut Emulator, count: Option<usize>) -> Result<(), DebugError> {
+        let reason = match count {
+            Some(count) => match (0..count).all(|_| emu.step_back()) {
+                true => None,
+                false => Some(StopReason::HistoryExhausted),
+            },
+            None => Some(emu.run_back()),
+        };
+        self.report(reason)?;
+        self.show(emu)?;
+        Ok(())
+    }
+
+    /// Reports why execution stopped.
+    fn report(&mut self, reason: Option<StopReason>) -> io::Result<()> {
+        match reason {
+            Some(StopReason::Breakpoint(pc)) => {
+                for (id, _) in self.breaks.iter().filter(|(_, &addr)| addr == pc) {
+                    writeln!(
+                        self.output,
+                        "Breakpoint {}, {}",
+                        id,
+                        self.symbols.symbolize(pc)
+                    )?;
+                }
+            }
+            Some(StopReason::Watchpoint {
+                addr,
+                old,
+                new,
+                pc,
+                access,
+            }) => {
+                let write = access == Watch::Write;
+                let hits: Vec<_> = self
+                    .watches
+                    .iter()
+                    .filter(|(_, wp)| wp.matches(addr, write))
+                    .map(|(&id, _)| id)
+                    .collect();
+                // Watchpoints set outside the debugger have no number
+                if hits.is_empty() {
+                    writeln!(self.output, "{}", reason.unwrap())?;
+                }
+                for id in hits {
+                    let by = self.symbols.symbolize(pc);
+                    match write {
+                        true => writeln!(
+                            self.output,
+                            "Watchpoint {}, {:#06x} written by {}: {:#06x} -> {:#06x}",
+                            id, addr, by, old, new
+                        )?,
+                        false => writeln!(
+                            self.output,
+                            "Watchpoint {}, {:#06x} read by {}: {:#06x}",
+                            id, addr, by, new
+                        )?,
+                    }
+                }
+            }
+            Some(StopReason::Fault(fault)) => writeln!(self.output, "{}", fault)?,
+            Some(reason @ StopReason::HistoryExhausted) => writeln!(self.output, "{}", reason)?,
+            _ => (),
+        }
+        Ok(())
+    }
+
+    /// Shows where execution has stopped.
+    fn show(&mut self, emu: &Emulator) -> io::Result<()> {
+        match emu.halted() {
+            true => writeln!(self.output, "Halted with status {}", emu.reg(0)),
+            false => self.disas(emu, emu.pc()),
+        }
+    }
+
+    fn examine(&mut self, emu: &Emulator, loc: &str, count: usize) -> Result<(), DebugError> {
+        const ROWSIZE: usize = 8;
+        let addr = self.locate(loc)?;
+        let words = (0..count)
+            .map(|idx| {
+                let addr = addr.wrapping_add((idx * WORDSIZE) as uarch);
+                emu.read(addr).ok_or(DebugError::BadAddress(addr))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        for (idx, row) in words.chunks(ROWSIZE).enumerate() {
+            write!(
+                self.output,
+                "{:#06x}:",
+                addr as usize + idx * ROWSIZE * WORDSIZE
+            )?;
+            for word in row {
+                write!(self.output, " {:04x}", word)?;
+            }
+            writeln!(self.output)?;
+        }
+        Ok(())
+    }
+
+    /// Disassembles the words surrounding the PC.
+    fn list(&mut self, emu: &Emulator, count: usize) -> io::Result<()> {
+        let pc = emu.pc() as usize;
+        let start = pc.saturating_sub(count * WORDSIZE);
+        let end = (pc + (count + 1) * WORDSIZE).min(RAMSIZE);
+        for addr in (start..end).step_by(WORDSIZE) {
+            self.disas(emu, addr as uarch)?;
+        }
+        Ok(())
+    }
+
+    fn disas(&mut self, emu: &Emulator, addr: uarch) -> io::Result<()> {
+        let marker = match addr == emu.pc() {
+            true => "=>",
+            false => "  ",
+        };
+        match emu.read(addr) {
+            Some(word) => writeln!(
+                self.output,
+                "{} {}: {:04x}  {}",
+                marker,
+                self.symbols.symbolize(addr),
+                word,
+                match Instruction::decode(word, emu.encoding()) {
+                    Ok(instr) => instr.to_string(),
+                    Err(_) => "(undefined)".to_string(),
+                }
+            ),
+            None => writeln!(
+                self.output,
+                "{} {}: ????",
+                marker,
+                self.symbols.symbolize(addr)
+            ),
+        }
+    }
+
+    /// Describes what a watchpoint watches.
+    fn describe(&self, wp: &Watchpoint) -> String {
+        let what = match wp.kind {
+            Watch::Read => "reads from",
+            Watch::Write => "writes to",
+            Watch::Access => "accesses to",
+        };
+        format!(
+            "{} {} ({} bytes)",
+            what,
+            self.symbols.symbolize(wp.range.start as uarch),
+            wp.range.len()
+        )
+    }
+
+    /// Resolves a location to an address.
+    fn locate(&self, loc: &str) -> Result<uarch, DebugError> {
+        self.symbols
+            .resolve(loc)
+            .ok_or_else(|| DebugError::BadLocation(loc.to_string()))
+    }
+}
+
+impl Default for Debugger<Lines, Stdout> {
+    fn default() -> Self {
+        Self::new(Lines::default(), io::stdout())
+    }
+}
+
+/// Standard input, read a line at a time.
+///
+/// Unlike [`io::StdinLock`], standard input is only locked while a line is
+/// being read, so that the program being debugged can also read from it.
+#[derive(Debug, Default)]
+pub struct Lines {
+    line: Vec<u8>,
+    pos: usize,
+}
+
+impl Read for Lines {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.fill_buf()?.read(buf)?;
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl BufRead for Lines {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos == self.line.len() {
+            self.line.clear();
+            self.pos = 0;
+            io::stdin().lock().read_until(b'\n', &mut self.line)?;
+        }
+        Ok(&self.line[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.line.len());
+    }
+}
+
+/// Parses a numeric command argument.
+fn number(s: &str) -> Result<uarch, DebugError> {
+    parse_num(s).ok_or_else(|| DebugError::BadNumber(s.to_string()))
+}
+
+/// Parses a register argument.
+fn register(s: &str) -> Result<uarch, DebugError> {
+    parse_reg(s).ok_or_else(|| DebugError::BadRegister(s.to_string()))
+}
+
+#[derive(Debug)]
+pub enum DebugError {
+    BadCommand(String),
+    BadNumber(String),
+    BadRegister(String),
+    BadLocation(String),
+    BadAddress(uarch),
+    NoBreakpoint(usize),
+    Io(io::Error),
+}
+
+impl Display for DebugError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::BadCommand(cmd) => format!("Unknown command `{}`; try `help`", cmd),
+                Self::BadNumber(num) => format!("Could not parse number from `{}`", num),
+                Self::BadRegister(reg) => format!("Unknown register `{}`", reg),
+                Self::BadLocation(loc) => format!("Unknown location `{}`", loc),
+                Self::BadAddress(addr) => format!("Cannot access memory at {:#06x}", addr),
+                Self::NoBreakpoint(id) => format!("No breakpoint {}", id),
+                Self::Io(err) => format!("{}", err),
+            }
+        )
+    }
+}
+
+impl Error for DebugError {}
+
+impl From<io::Error> for DebugError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn debug(emu: &mut Emulator, script: &str) -> String {
+        let mut output = Vec::new();
+        let mut dbg = Debugger::new(script.as_bytes(), &mut output);
+        dbg.symbols.insert("_main", 0x0000);
+        dbg.run(emu).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn session() {
+        let mut e = Emulator::new();
+        // mov r0, 0x1; add r0, 0x2; add r0, 0x3; hlt
+        for (addr, word) in [0x7081, 0xc082, 0xc083, 0x0c00].into_iter().enumerate() {
+            e.write((addr * WORDSIZE) as uarch, word).unwrap();
+        }
+        let out = debug(&mut e, "b 0x4\nc\np a0\nset a0 0x10\n\nx _main 2\nc\n");
+        assert!(out.contains("Breakpoint 1, 0x0004 <_main+4>"));
+        assert!(out.contains("a0 = 0x0003 (3)"));
+        assert!(out.contains("0x0000: 7081 c082"));
+        assert!(out.contains("Halted with status 19"));
+        // Ensure execution can be reversed
+        let mut e = Emulator::new();
+        e.set_history(0x10);
+        // mov r1, 0x20; str r1, r1; add r1, 0x2; str r1, r1; hlt
+        for (addr, word) in [0x71a0, 0x2101, 0xc182, 0x2101, 0x0c00]
+            .into_iter()
+            .enumerate()
+        {
+            e.write((addr * WORDSIZE) as uarch, word).unwrap();
+        }
+        let out = debug(
+            &mut e,
+            "b 0x4
+c
+c
+lastw 0x20
+rc
+p a1
+rs 5
+lastw 0x22
+",
+        );
+        assert!(out.contains("0x0020 written by 0x0002 <_main+2> at cycle 1: 0x0000 -> 0x0020"));
+        assert!(out.contains("Breakpoint 1, 0x0004 <_main+4>"));
+        assert!(out.contains("a1 = 0x0020 (32)"));
+        assert!(out.contains("Reached the start of the recorded history"));
+        assert!(out.contains("No recorded write to 0x0022"));
+        // Ensure watchpoints stop execution
+        let mut e = Emulator::new();
+        // mov r1, 0x20; str r1, r1; add r1, 0x2; str r1, r1; hlt
+        for (addr, word) in [0x71a0, 0x2101, 0xc182, 0x2101, 0x0c00]
+            .into_iter()
+            .enumerate()
+        {
+            e.write((addr * WORDSIZE) as uarch, word).unwrap();
+        }
+        let out = debug(&mut e, "wa 0x22\nrw 0x20 4\nwa\nc\nd 1\nc\n");
+        assert!(out.contains("Watchpoint 1 on writes to 0x0022 <_main+34> (2 bytes)"));
+        assert!(out.contains("2: reads from 0x0020 <_main+32> (4 bytes)"));
+        assert!(out.contains("Watchpoint 1, 0x0022 written by 0x0006 <_main+6>: 0x0000 -> 0x0022"));
+        assert!(out.contains("Halted with status 0"));
+        // Ensure bad input is reported without stopping the session
+        let out = debug(&mut Emulator::new(), "frob\nset r16 0\nx 0x1\nq\n");
+        assert!(out.contains("Unknown command `frob`"));
+        assert!(out.contains("Unknown register `r16`"));
+        assert!(out.contains("Cannot access memory at 0x0001"));
+    }
+}
@@ -0,0 +1,633 @@
+//! GDB remote serial protocol stub.
+//!
+//! The stub lets any client speaking the [remote serial protocol][rsp] drive
+//! an [`Emulator`] over a TCP or Unix socket. It supports reading and writing
+//! registers and memory, software breakpoints, watchpoints, single-stepping and
+//! continuing.
+//! When the emulator records its history, it also supports reverse stepping
+//! and continuing, and the `monitor lastwrite ADDR` command to find the last
+//! recorded write to an address.
+//!
+//! Registers are numbered `R0`-`R15`, followed by `SR`, as described by the
+//! target description the stub serves to clients.
+//!
+//! [rsp]: https://sourceware.org/gdb/onlinedocs/gdb/Remote-Protocol.html
+
+use std::fs;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::os::unix::net::{UnixListener, UnixStream};
+
+use log::info;
+
+use isa::{reg, uarch, WORDSIZE};
+
+use crate::{Emulator, StopReason, Watch};
+
+/// Index of the status register, following the general purpose registers.
+const SR: usize = reg::COUNT;
+/// Number of instructions to run between checks for an interrupt.
+const POLL: u64 = 0x400;
+
+/// Signal reported when stopped by a trap.
+const SIGTRAP: u8 = 5;
+/// Signal reported when stopped by the client.
+const SIGINT: u8 = 2;
+/// Signal reported when stopped by a fault.
+const SIGSEGV: u8 = 11;
+
+/// A bidirectional stream to a client.
+pub trait Connection: Read + Write {
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()>;
+}
+
+impl Connection for TcpStream {
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        TcpStream::set_nonblocking(self, nonblocking)
+    }
+}
+
+impl Connection for UnixStream {
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        UnixStream::set_nonblocking(self, nonblocking)
+    }
+}
+
+/// Waits for a client to connect.
+///
+/// The target is either a TCP port on the local host, or the path of a Unix
+/// socket.
+pub fn accept(target: &str) -> io::Result<Box<dyn Connection>> {
+    match target.parse::<u16>() {
+        Ok(port) => {
+            let listener = TcpListener::bind(("127.0.0.1", port))?;
+            info!("Listening on {}", listener.local_addr()?);
+            let (stream, _) = listener.accept()?;
+            stream.set_nodelay(true)?;
+            Ok(Box::new(stream))
+        }
+        Err(_) => {
+            let listener = UnixListener::bind(target)?;
+            info!("Listening on {}", target);
+            let (stream, _) = listener.accept()?;
+            // Clean up the socket once connected
+            fs::remove_file(target)?;
+            Ok(Box::new(stream))
+        }
+    }
+}
+
+pub struct Stub {
+    conn: Box<dyn Connection>,
+    ack: bool,
+}
+
+impl Stub {
+    pub fn new(conn: Box<dyn Connection>) -> Self {
+        Self { conn, ack: true }
+    }
+
+    /// Serves requests until the client detaches or disconnects.
+    pub fn serve(&mut self, emu: &mut Emulator) -> io::Result<()> {
+        while let Some(packet) = self.recv()? {
+            let packet = String::from_utf8_lossy(&packet).into_owned();
+            let reply = match self.handle(emu, &packet)? {
+                Some(reply) => reply,
+                // Only detaching is acknowledged; killing expects no reply
+                None if packet == "k" => return Ok(()),
+                None => return self.send("OK"),
+            };
+            // Stop acknowledging once the client has asked to
+            if packet == "QStartNoAckMode" {
+                self.ack = false;
+            }
+            self.send(&reply)?;
+        }
+        Ok(())
+    }
+
+    /// Handles a packet, returning the reply, or `None` to end the session.
+    fn handle(&mut self, emu: &mut Emulator, packet: &str) -> io::Result<Option<String>> {
+        let (cmd, args) = packet.split_at(packet.len().min(1));
+        let reply = match cmd {
+            "?" => stop(SIGTRAP),
+            "g" => (0..=SR).map(|idx| hex(read_reg(emu, idx))).collect(),
+            "G" => match words(args) {
+                Some(values) if values.len() == SR + 1 => {
+                    for (idx, value) in values.into_iter().enumerate() {
+                        write_reg(emu, idx, value);
+                    }
+                    "OK".to_string()
+                }
+                _ => error(),
+            },
+            "p" => match parse(args) {
+                Some(idx) if idx <= SR => hex(read_reg(emu, idx)),
+                _ => error(),
+            },
+            "P" => match args.split_once('=').and_then(|(idx, value)| {
+                Some((parse(idx).filter(|&idx| idx <= SR)?, words(value)?))
+            }) {
+                Some((idx, value)) if value.len() == 1 => {
+                    write_reg(emu, idx, value[0]);
+                    "OK".to_string()
+                }
+                _ => error(),
+            },
+            "m" => match range(args).and_then(|(addr, len)| read_mem(emu, addr, len)) {
+                Some(bytes) => bytes.iter().map(|byte| format!("{:02x}", byte)).collect(),
+                None => error(),
+            },
+            "M" => match args.split_once(':').and_then(|(range_, data)| {
+                let (addr, len) = range(range_)?;
+                let bytes = bytes(data).filter(|bytes| bytes.len() == len)?;
+                write_mem(emu, addr, &bytes)
+            }) {
+                Some(()) => "OK".to_string(),
+                None => error(),
+            },
+            "Z" | "z" => match args.split(',').collect::<Vec<_>>()[..] {
+                ["0", addr, _] => match parse(addr) {
+                    Some(addr) => {
+                        match cmd {
+                            "Z" => emu.set_breakpoint(addr as uarch),
+                            _ => drop(emu.clear_breakpoint(addr as uarch)),
+                        };
+                        "OK".to_string()
+                    }
+                    None => error(),
+                },
+                [kind @ ("2" | "3" | "4"), addr, len] => match (parse(addr), parse(len)) {
+                    (Some(addr), Some(len)) => {
+                        let kind = match kind {
+                            "2" => Watch::Write,
+                            "3" => Watch::Read,
+                            _ => Watch::Access,
+                        };
+                        let range = addr..addr + len;
+                        match cmd {
+                            "Z" => emu.set_watchpoint(range, kind),
+                            _ => drop(emu.clear_watchpoint(range, kind)),
+                        };
+                        "OK".to_string()
+                    }
+                    _ => error(),
+                },
+                // Hardware breakpoints are not supported
+                _ => String::new(),
+            },
+            "c" => self.resume(emu, false)?,
+            "s" => self.resume(emu, true)?,
+            "b" => match args {
+                "c" => {
+                    let reason = emu.run_back();
+                    reply(emu, reason)
+                }
+                "s" => match emu.step_back() {
+                    true => stop(SIGTRAP),
+                    false => reply(emu, StopReason::HistoryExhausted),
+                },
+                _ => String::new(),
+            },
+            "D" | "k" => return Ok(None),
+            "H" => "OK".to_string(),
+            _ => match packet {
+                "vCont?" => "vCont;c;C;s;S".to_string(),
+                _ if packet.starts_with("vCont;c") || packet.starts_with("vCont;C") => {
+                    self.resume(emu, false)?
+                }
+                _ if packet.starts_with("vCont;s") || packet.starts_with("vCont;S") => {
+                    self.resume(emu, true)?
+                }
+                _ if packet.starts_with("qSupported") => {
+                    "PacketSize=1000;qXfer:features:read+;QStartNoAckMode+;ReverseStep+;ReverseContinue+"
+                        .to_string()
+                }
+                _ if packet.starts_with("qXfer:features:read:target.xml:") => {
+                    let (_, range_) = packet.rsplit_once(':').unwrap_or_default();
+                    match range(range_) {
+                        Some((off, len)) => {
+                            let xml = target();
+                            let chunk = xml.get(off..xml.len().min(off + len)).unwrap_or("");
+                            match off + len < xml.len() {
+                                true => format!("m{}", chunk),
+                                false => format!("l{}", chunk),
+                            }
+                        }
+                        None => error(),
+                    }
+                }
+                _ if packet.starts_with("qRcmd,") => {
+                    match bytes(&packet[6..]).and_then(|cmd| String::from_utf8(cmd).ok()) {
+                        Some(cmd) => monitor(emu, &cmd)
+                            .bytes()
+                            .map(|byte| format!("{:02x}", byte))
+                            .collect(),
+                        None => error(),
+                    }
+                }
+                "QStartNoAckMode" => "OK".to_string(),
+                "qAttached" => "1".to_string(),
+                "qC" => "QC1".to_string(),
+                "qfThreadInfo" => "m1".to_string(),
+                "qsThreadInfo" => "l".to_string(),
+                _ => String::new(),
+            },
+        };
+        Ok(Some(reply))
+    }
+
+    /// Resumes execution, returning the stop reply.
+    fn resume(&mut self, emu: &mut Emulator, step: bool) -> io::Result<String> {
+        loop {
+            match emu.run_for(if step { 1 } else { POLL }) {
+                StopReason::CycleLimit if !step => {
+                    // Periodically check if the client wants to interrupt
+                    if self.interrupted()? {
+                        return Ok(stop(SIGINT));
+                    }
+                }
+                reason => return Ok(reply(emu, reason)),
+            }
+        }
+    }
+
+    /// Checks for a pending interrupt request from the client.
+    fn interrupted(&mut self) -> io::Result<bool> {
+        self.conn.set_nonblocking(true)?;
+        let mut byte = [0];
+        let res = self.conn.read(&mut byte);
+        self.conn.set_nonblocking(false)?;
+        match res {
+            Ok(1) => Ok(byte[0] == 0x03),
+            Ok(_) => Ok(false),
+            Err(err) if err.kind() == ErrorKind::WouldBlock => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Receives the next packet, or `None` once the client disconnects.
+    fn recv(&mut self) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            // Wait for the start of a packet
+            match self.byte()? {
+                Some(b'$') => (),
+                Some(_) => continue,
+                None => return Ok(None),
+            }
+            // Read its data, unescaping as needed
+            let mut data = Vec::new();
+            let mut sum: u8 = 0;
+            loop {
+                let byte = self.byte()?.ok_or(ErrorKind::UnexpectedEof)?;
+                if byte == b'#' {
+                    break;
+                }
+                sum = sum.wrapping_add(byte);
+                match byte {
+                    b'}' => {
+                        let byte = self.byte()?.ok_or(ErrorKind::UnexpectedEof)?;
+                        sum = sum.wrapping_add(byte);
+                        data.push(byte ^ 0x20);
+                    }
+                    _ => data.push(byte),
+                }
+            }
+            // Verify its checksum
+            let check = [
+                self.byte()?.ok_or(ErrorKind::UnexpectedEof)?,
+                self.byte()?.ok_or(ErrorKind::UnexpectedEof)?,
+            ];
+            let valid = std::str::from_utf8(&check)
+                .ok()
+                .and_then(|check| u8::from_str_radix(check, 16).ok())
+                == Some(sum);
+            if self.ack {
+                self.conn.write_all(if valid { b"+" } else { b"-" })?;
+                self.conn.flush()?;
+            }
+            if valid {
+                return Ok(Some(data));
+            }
+        }
+    }
+
+    /// Sends a packet, retrying until acknowledged.
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        let mut packet = Vec::with_capacity(data.len() + 4);
+        packet.push(b'$');
+        for &byte in data.as_bytes() {
+            match byte {
+                b'#' | b'$' | b'}' | b'*' => packet.extend([b'}', byte ^ 0x20]),
+                _ => packet.push(byte),
+            }
+        }
+        let sum = packet[1..]
+            .iter()
+            .fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+        packet.extend(format!("#{:02x}", sum).bytes());
+        loop {
+            self.conn.write_all(&packet)?;
+            self.conn.flush()?;
+            if !self.ack {
+                return Ok(());
+            }
+            // Wait for an acknowledgement
+            loop {
+                match self.byte()? {
+                    Some(b'+') => return Ok(()),
+                    Some(b'-') => break,
+                    Some(_) => continue,
+                    None => return Err(ErrorKind::UnexpectedEof.into()),
+                }
+            }
+        }
+    }
+
+    fn byte(&mut self) -> io::Result<Option<u8>> {
+        let mut byte = [0];
+        match self.conn.read(&mut byte)? {
+            0 => Ok(None),
+            _ => Ok(Some(byte[0])),
+        }
+    }
+}
+
+/// Describes the target's registers to the client.
+fn target() -> String {
+    let mut xml = String::from(concat!(
+        "<?xml version=\"1.0\"?>\n",
+        "<!DOCTYPE target SYSTEM \"gdb-target.dtd\">\n",
+        "<target version=\"1.0\">\n",
+        "  <feature name=\"org.kap16.core\">\n",
+        "    <flags id=\"sr_flags\" size=\"2\">\n",
+        "      <field name=\"Z\" start=\"0\" end=\"0\"/>\n",
+        "      <field name=\"N\" start=\"1\" end=\"1\"/>\n",
+        "      <field name=\"V\" start=\"2\" end=\"2\"/>\n",
+        "      <field name=\"C\" start=\"3\" end=\"3\"/>\n",
+        "      <field name=\"I\" start=\"4\" end=\"4\"/>\n",
+        "    </flags>\n",
+    ));
+    for (idx, name) in reg::NAMES.iter().enumerate() {
+        let kind = match *name {
+            "sp" => "data_ptr",
+            "lr" | "pc" => "code_ptr",
+            _ => "uint16",
+        };
+        xml.push_str(&format!(
+            "    <reg name=\"{}\" bitsize=\"16\" type=\"{}\" regnum=\"{}\"/>\n",
+            name, kind, idx
+        ));
+    }
+    xml.push_str(&format!(
+        "    <reg name=\"sr\" bitsize=\"16\" type=\"sr_flags\" regnum=\"{}\"/>\n",
+        SR
+    ));
+    xml.push_str("  </feature>\n</target>\n");
+    xml
+}
+
+/// Formats the stop reply for a reason execution stopped.
+fn reply(emu: &Emulator, reason: StopReason) -> String {
+    match reason {
+        StopReason::Halt(status) => format!("W{:02x}", status as u8),
+        StopReason::Fault(_) => stop(SIGSEGV),
+        // Clients find the watchpoint hit from its address and kind
+        StopReason::Watchpoint { addr, access, .. } => {
+            let write = access == Watch::Write;
+            let exact = emu
+                .watchpoints()
+                .iter()
+                .any(|wp| wp.kind == access && wp.matches(addr, write));
+            let kind = match (exact, access) {
+                (true, Watch::Write) => "watch",
+                (true, _) => "rwatch",
+                (false, _) => "awatch",
+            };
+            format!("T{:02x}{}:{:x};", SIGTRAP, kind, addr)
+        }
+        StopReason::HistoryExhausted => format!("T{:02x}replaylog:begin;", SIGTRAP),
+        _ => stop(SIGTRAP),
+    }
+}
+
+/// Performs a `monitor` command, returning its output.
+fn monitor(emu: &Emulator, cmd: &str) -> String {
+    match cmd.split_whitespace().collect::<Vec<_>>()[..] {
+        ["lastwrite", addr] => {
+            let addr = addr.trim_start_matches("0x");
+            match uarch::from_str_radix(addr, 16).ok() {
+                Some(addr) => match emu.last_write(addr) {
+                    Some(write) => format!(
+                        "{:#06x} written by {:#06x} at cycle {}: {:#06x} -> {:#06x}\n",
+                        addr, write.pc, write.cycle, write.old, write.new
+                    ),
+                    None => format!("No recorded write to {:#06x}\n", addr),
+                },
+                None => "Usage: monitor lastwrite ADDR\n".to_string(),
+            }
+        }
+        _ => "Unknown monitor command; try `lastwrite ADDR`\n".to_string(),
+    }
+}
+
+fn stop(signal: u8) -> String {
+    format!("S{:02x}", signal)
+}
+
+fn error() -> String {
+    "E01".to_string()
+}
+
+fn read_reg(emu: &Emulator, idx: usize) -> uarch {
+    match idx {
+        SR => emu.sr(),
+        idx => emu.reg(idx as uarch),
+    }
+}
+
+fn write_reg(emu: &mut Emulator, idx: usize, value: uarch) {
+    match idx {
+        SR => emu.set_sr(value),
+        idx => emu.set_reg(idx as uarch, value),
+    }
+}
+
+/// Reads bytes from memory, which is accessed a word at a time.
+fn read_mem(emu: &Emulator, addr: usize, len: usize) -> Option<Vec<u8>> {
+    (addr..addr.checked_add(len)?)
+        .map(|addr| {
+            let word = emu.read(u16::try_from(addr - addr % WORDSIZE).ok()?)?;
+            Some(word.to_le_bytes()[addr % WORDSIZE])
+        })
+        .collect()
+}
+
+/// Writes bytes to memory, which is accessed a word at a time.
+fn write_mem(emu: &mut Emulator, addr: usize, bytes: &[u8]) -> Option<()> {
+    // Ensure the whole range is writable before modifying it
+    read_mem(emu, addr, bytes.len())?;
+    for (idx, &byte) in bytes.iter().enumerate() {
+        let addr = addr + idx;
+        let base = (addr - addr % WORDSIZE) as uarch;
+        let mut word = emu.read(base)?.to_le_bytes();
+        word[addr % WORDSIZE] = byte;
+        emu.write(base, uarch::from_le_bytes(word))?;
+    }
+    Some(())
+}
+
+/// Formats a register value in target byte order.
+fn hex(value: uarch) -> String {
+    value
+        .to_le_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn parse(s: &str) -> Option<usize> {
+    usize::from_str_radix(s, 16).ok()
+}
+
+/// Parses an `addr,len` pair.
+fn range(s: &str) -> Option<(usize, usize)> {
+    let (addr, len) = s.split_once(',')?;
+    Some((parse(addr)?, parse(len)?))
+}
+
+fn bytes(s: &str) -> Option<Vec<u8>> {
+    (0..s.len())
+        .step_by(2)
+        .map(|idx| u8::from_str_radix(s.get(idx..idx + 2)?, 16).ok())
+        .collect()
+}
+
+/// Parses register values in target byte order.
+fn words(s: &str) -> Option<Vec<uarch>> {
+    let bytes = bytes(s)?;
+    (bytes.len() % WORDSIZE == 0).then(|| {
+        bytes
+            .chunks(WORDSIZE)
+            .map(|word| uarch::from_le_bytes([word[0], word[1]]))
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    /// Sends a packet without acknowledgements, returning the reply.
+    fn request(stream: &mut TcpStream, data: &str) -> String {
+        let sum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+        write!(stream, "${}#{:02x}", data, sum).unwrap();
+        let mut reply = Vec::new();
+        let mut byte = [0];
+        while byte[0] != b'#' {
+            stream.read_exact(&mut byte).unwrap();
+            reply.push(byte[0]);
+        }
+        stream.read_exact(&mut [0; 2]).unwrap();
+        String::from_utf8(reply[1..reply.len() - 1].to_vec()).unwrap()
+    }
+
+    /// Serves a program to a client, once it negotiates out of
+    /// acknowledgements.
+    fn serve(e: &mut Emulator, client: impl FnOnce(&mut TcpStream) + Send + 'static) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let client = thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            stream.set_nodelay(true).unwrap();
+            // Negotiate out of acknowledgements
+            write!(stream, "$QStartNoAckMode#b0").unwrap();
+            let mut ack = [0; 1];
+            stream.read_exact(&mut ack).unwrap();
+            assert_eq!(&ack, b"+");
+            let mut reply = [0; 6];
+            stream.read_exact(&mut reply).unwrap();
+            assert_eq!(&reply, b"$OK#9a");
+            client(&mut stream);
+        });
+        let (stream, _) = listener.accept().unwrap();
+        stream.set_nodelay(true).unwrap();
+        Stub::new(Box::new(stream)).serve(e).unwrap();
+        client.join().unwrap();
+    }
+
+    #[test]
+    fn session() {
+        let mut e = Emulator::new();
+        e.set_history(0x10);
+        // mov r0, 0x1; add r0, 0x2; add r0, 0x3; hlt
+        for (addr, word) in [0x7081, 0xc082, 0xc083, 0x0c00].into_iter().enumerate() {
+            e.write((addr * WORDSIZE) as uarch, word).unwrap();
+        }
+        serve(&mut e, |stream| {
+            // Inspect the target
+            let xml = request(stream, "qXfer:features:read:target.xml:0,1000");
+            assert!(xml.starts_with("l<?xml"));
+            assert!(xml.contains("name=\"sr\""));
+            // Break after the first instruction
+            assert_eq!(request(stream, "Z0,2,2"), "OK");
+            assert_eq!(request(stream, "c"), "S05");
+            assert_eq!(request(stream, "p0"), "0100");
+            assert_eq!(request(stream, "p f"), "E01");
+            assert_eq!(request(stream, "pf"), "0200");
+            // Modify state
+            assert_eq!(request(stream, "P0=0500"), "OK");
+            assert_eq!(request(stream, "M2,2:85c0"), "OK");
+            assert_eq!(request(stream, "m2,3"), "85c083");
+            assert_eq!(request(stream, "m3ffe,4"), "E01");
+            // Step, then step back
+            assert_eq!(request(stream, "s"), "S05");
+            assert_eq!(request(stream, "p0"), "0a00");
+            assert_eq!(request(stream, "bs"), "S05");
+            assert_eq!(request(stream, "p0"), "0500");
+            assert_eq!(request(stream, "bc"), "T05replaylog:begin;");
+            assert_eq!(request(stream, "pf"), "0000");
+            // Ask who last wrote memory
+            let out = request(stream, "qRcmd,6c61737477726974652030");
+            assert_eq!(bytes(&out).unwrap(), b"No recorded write to 0x0000\n");
+            // Set and remove a watchpoint
+            assert_eq!(request(stream, "Z2,20,2"), "OK");
+            assert_eq!(request(stream, "z2,20,2"), "OK");
+            assert_eq!(request(stream, "Z1,2,2"), "");
+            // Run to completion, through the modified instruction
+            assert_eq!(request(stream, "c"), "S05");
+            assert_eq!(request(stream, "c"), "W09");
+            // Kill the target, expecting no reply
+            write!(stream, "$k#6b").unwrap();
+            let mut rest = Vec::new();
+            stream.read_to_end(&mut rest).unwrap();
+            assert!(rest.is_empty());
+        });
+    }
+
+    #[test]
+    fn watch() {
+        let mut e = Emulator::new();
+        e.set_history(0x10);
+        // .word 0x0002; mov sp, 0x30; mov r0, 0x5; push r0; pop r1; hlt
+        let rom = [
+            0x02, 0x00, 0xb0, 0x7d, 0x85, 0x70, 0x40, 0x20, 0x40, 0x31, 0x00, 0x0c,
+        ];
+        e.load_bytes(&rom).unwrap();
+        serve(&mut e, |stream| {
+            // Ensure each kind of watchpoint is reported as such
+            assert_eq!(request(stream, "Z2,2e,2"), "OK");
+            assert_eq!(request(stream, "c"), "T05watch:2e;");
+            assert_eq!(request(stream, "z2,2e,2"), "OK");
+            assert_eq!(request(stream, "bs"), "S05");
+            assert_eq!(request(stream, "Z4,2e,2"), "OK");
+            assert_eq!(request(stream, "c"), "T05awatch:2e;");
+            assert_eq!(request(stream, "z4,2e,2"), "OK");
+            assert_eq!(request(stream, "Z3,2e,2"), "OK");
+            assert_eq!(request(stream, "c"), "T05rwatch:2e;");
+            assert_eq!(request(stream, "c"), "W05");
+        });
+    }
+}
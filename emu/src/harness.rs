@@ -0,0 +1,424 @@
+//! Declarative test cases.
+//!
+//! A [`Suite`] is loaded from a TOML file holding a list of cases. Each case
+//! loads a program, sets up the machine, then runs it within a cycle budget.
+//! A case passes if the program halts and its final state matches what was
+//! expected:
+//!
+//! ```toml
+//! [[case]]
+//! name = "fib"
+//! src = "src/bin/fib.s"
+//! cycles = 1000
+//!
+//! [case.expect.regs]
+//! g0 = 0xda31
+//!
+//! [case.expect.mem]
+//! 0x3ffe = [0x0000, 0x0001]
+//! ```
+//!
+//! | Field           | Contents                                           |
+//! | --------------- | -------------------------------------------------- |
+//! | `name`          | name to report the case by                         |
+//! | `rom`           | image to load                                      |
+//! | `src`           | source to assemble instead of loading an image     |
+//! | `include`       | directories to search for files included by `src`  |
+//! | `cycles`        | cycle budget (default 1000000)                     |
+//! | `input`         | console input                                      |
+//! | `uart_input`    | UART input                                         |
+//! | `regs`          | initial registers, including `sr`                  |
+//! | `mem`           | initial memory, as words from each address         |
+//! | `expect.status` | exit status                                        |
+//! | `expect.regs`   | final registers, including `sr`                    |
+//! | `expect.flags`  | final status register flags                        |
+//! | `expect.mem`    | final memory, as words from each address           |
+//! | `expect.output` | console and UART output                            |
+//!
+//! Paths are relative to the suite's file.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::fs;
+use std::io::{self, Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use asm::Assembler;
+use serde::Deserialize;
+
+use crate::sym::{parse_num, parse_reg};
+use crate::uart::Uart;
+use crate::{uarch, Console, Emulator, Flag, StopReason, WORDSIZE};
+
+/// Cycle budget of cases which do not set one.
+const CYCLES: u64 = 1_000_000;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Suite {
+    #[serde(default, rename = "case")]
+    pub cases: Vec<Case>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Case {
+    pub name: String,
+    pub rom: Option<PathBuf>,
+    pub src: Option<PathBuf>,
+    #[serde(default)]
+    pub include: Vec<PathBuf>,
+    #[serde(default = "cycles")]
+    pub cycles: u64,
+    #[serde(default)]
+    pub input: String,
+    #[serde(default)]
+    pub uart_input: String,
+    #[serde(default)]
+    pub regs: BTreeMap<String, uarch>,
+    #[serde(default)]
+    pub mem: BTreeMap<String, Vec<uarch>>,
+    #[serde(default)]
+    pub expect: Expect,
+}
+
+/// Expected final state of a case.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Expect {
+    pub status: Option<uarch>,
+    #[serde(default)]
+    pub regs: BTreeMap<String, uarch>,
+    #[serde(default)]
+    pub flags: BTreeMap<String, bool>,
+    #[serde(default)]
+    pub mem: BTreeMap<String, Vec<uarch>>,
+    pub output: Option<String>,
+}
+
+fn cycles() -> u64 {
+    CYCLES
+}
+
+impl Suite {
+    /// Loads a suite from a file.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut suite: Self = toml::from_str(&fs::read_to_string(path)?)?;
+        // Resolve paths relative to the suite
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        for case in &mut suite.cases {
+            let paths = case.rom.iter_mut().chain(&mut case.src);
+            for path in paths.chain(&mut case.include) {
+                *path = dir.join(&*path);
+            }
+        }
+        Ok(suite)
+    }
+}
+
+impl Case {
+    /// Runs the case, returning its outcome.
+    ///
+    /// Fails if the case could not be set up.
+    pub fn run(&self) -> Result<Outcome, Box<dyn Error>> {
+        // Build the program's image
+        let image = match (&self.rom, &self.src) {
+            (Some(rom), None) => fs::read(rom)?,
+            (None, Some(src)) => {
+                let mut asm = Assembler::new();
+                for path in &self.include {
+                    asm.include(path);
+                }
+                asm.src(src)?;
+                asm.asm()?;
+                asm.link()?;
+                asm.bytes()
+            }
+            _ => return Err(CaseError::Program.into()),
+        };
+        // Set up the machine
+        let mut e = Emulator::new();
+        let output = Capture::default();
+        let input = Cursor::new(self.input.clone().into_bytes());
+        e.set_sys_handler(Console::new(input, output.clone()));
+        let input = Cursor::new(self.uart_input.clone().into_bytes());
+        e.set_uart(Uart::connect(input, output.clone()));
+        e.load_bytes(&image)?;
+        for (name, &value) in &self.regs {
+            match name.as_str() {
+                "sr" => e.set_sr(value),
+                name => e.set_reg(register(name)?, value),
+            }
+        }
+        for (addr, words) in &self.mem {
+            let addr = address(addr)?;
+            for (idx, &word) in words.iter().enumerate() {
+                let addr = addr.wrapping_add((idx * WORDSIZE) as uarch);
+                e.write(addr, word).ok_or(CaseError::BadAddress(addr))?;
+            }
+        }
+        // Run the program
+        let reason = e.run_for(self.cycles);
+        // Compare its final state
+        let mut diffs = Vec::new();
+        let mut check = |what: String, expected: String, found: String| {
+            if expected != found {
+                diffs.push(Diff {
+                    what,
+                    expected,
+                    found,
+                });
+            }
+        };
+        match reason {
+            StopReason::Halt(status) => {
+                if let Some(expected) = self.expect.status {
+                    check("status".to_string(), word(expected), word(status));
+                }
+            }
+            reason => check("stop".to_string(), "halt".to_string(), reason.to_string()),
+        }
+        for (name, &expected) in &self.expect.regs {
+            let found = match name.as_str() {
+                "sr" => e.sr(),
+                name => e.reg(register(name)?),
+            };
+            check(name.clone(), word(expected), word(found));
+        }
+        for (name, &expected) in &self.expect.flags {
+            let flag = Flag::lookup(name).ok_or_else(|| CaseError::BadFlag(name.clone()))?;
+            let found = e.flag(flag);
+            check(name.clone(), expected.to_string(), found.to_string());
+        }
+        for (addr, words) in &self.expect.mem {
+            let addr = address(addr)?;
+            for (idx, &expected) in words.iter().enumerate() {
+                let addr = addr.wrapping_add((idx * WORDSIZE) as uarch);
+                let found = e.read(addr).map_or_else(|| "????".to_string(), word);
+                check(format!("{:#06x}", addr), word(expected), found);
+            }
+        }
+        if let Some(expected) = &self.expect.output {
+            let found = String::from_utf8_lossy(&output.0.borrow()).into_owned();
+            check(
+                "output".to_string(),
+                format!("{:?}", expected),
+                format!("{:?}", found),
+            );
+        }
+        Ok(Outcome {
+            diffs,
+            state: e.to_string(),
+        })
+    }
+}
+
+/// Result of running a case.
+#[derive(Debug)]
+pub struct Outcome {
+    /// Differences from the expected final state.
+    pub diffs: Vec<Diff>,
+    /// Final state of the processor.
+    pub state: String,
+}
+
+impl Outcome {
+    pub fn passed(&self) -> bool {
+        self.diffs.is_empty()
+    }
+}
+
+/// A difference from the expected final state.
+#[derive(Debug, Eq, PartialEq)]
+pub struct Diff {
+    pub what: String,
+    pub expected: String,
+    pub found: String,
+}
+
+impl Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: expected {}, found {}",
+            self.what, self.expected, self.found
+        )
+    }
+}
+
+/// Console output shared with the test runner.
+#[derive(Clone, Debug, Default)]
+struct Capture(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Capture {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Formats a word for comparison.
+fn word(word: uarch) -> String {
+    format!("{:#06x}", word)
+}
+
+/// Parses a register.
+fn register(name: &str) -> Result<uarch, CaseError> {
+    parse_reg(name).ok_or_else(|| CaseError::BadRegister(name.to_string()))
+}
+
+/// Parses an address.
+fn address(addr: &str) -> Result<uarch, CaseError> {
+    parse_num(addr).ok_or_else(|| CaseError::BadNumber(addr.to_string()))
+}
+
+#[derive(Debug)]
+pub enum CaseError {
+    /// The case does not name exactly one of a ROM or source.
+    Program,
+    BadRegister(String),
+    BadFlag(String),
+    BadNumber(String),
+    BadAddress(uarch),
+}
+
+impl Display for CaseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Program => "Case must name either a `rom` or a `src`".to_string(),
+                Self::BadRegister(reg) => format!("Unknown register `{}`", reg),
+                Self::BadFlag(flag) => format!("Unknown flag `{}`", flag),
+                Self::BadNumber(num) => format!("Could not parse number from `{}`", num),
+                Self::BadAddress(addr) => format!("Cannot access memory at {:#06x}", addr),
+            }
+        )
+    }
+}
+
+impl Error for CaseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suite() {
+        // Ensure the example programs pass their regression tests
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../prog/tests.toml");
+        let suite = Suite::load(&path).unwrap();
+        assert!(!suite.cases.is_empty());
+        for case in &suite.cases {
+            let outcome = case.run().unwrap();
+            assert!(outcome.passed(), "{}: {:?}", case.name, outcome.diffs);
+        }
+    }
+
+    /// A temporary directory, removed once dropped.
+    struct TempDir(PathBuf);
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Runs the only case of a suite, written to a temporary directory along
+    /// with the files it loads.
+    fn run(name: &str, files: &[(&str, &[u8])], suite: &str) -> Outcome {
+        let dir =
+            TempDir(std::env::temp_dir().join(format!("harness-{}-{}", name, std::process::id())));
+        fs::create_dir_all(&dir.0).unwrap();
+        for (path, contents) in files {
+            fs::write(dir.0.join(path), contents).unwrap();
+        }
+        fs::write(dir.0.join("suite.toml"), suite).unwrap();
+        let suite = Suite::load(&dir.0.join("suite.toml")).unwrap();
+        suite.cases[0].run().unwrap()
+    }
+
+    #[test]
+    fn diff() {
+        // .word 0x0002; mov a0, 0x1; sys; hlt
+        let rom = [0x02, 0x00, 0x81, 0x70, 0x00, 0x08, 0x00, 0x0c];
+        let outcome = run(
+            "diff",
+            &[("putchar.rom", &rom)],
+            r#"
+            [[case]]
+            name = "putchar"
+            rom = "putchar.rom"
+            cycles = 2
+
+            [case.regs]
+            a1 = 0x0021
+
+            [case.expect]
+            output = "!"
+
+            [case.expect.regs]
+            a1 = 0x0021
+            g0 = 0x0001
+
+            [case.expect.flags]
+            z = true
+            "#,
+        );
+        // Ensure every difference is reported
+        let diffs: Vec<_> = outcome.diffs.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            diffs,
+            [
+                "stop: expected halt, found Cycle limit reached",
+                "g0: expected 0x0001, found 0x0000",
+                "z: expected true, found false",
+            ]
+        );
+        assert!(outcome.state.contains("R01: 0021"));
+    }
+
+    #[test]
+    fn uart() {
+        let src = "
+                ldr g0, =0xffd8
+                ldr g1, =0xffda
+            loop:
+                ldr a0, g1
+                tst a0, 0x1
+                ifeq
+                goto loop
+                ldr a0, g0
+                str a0, g0
+                cmp a0, 0xa
+                ifne
+                goto loop
+                mov a0, 0x0
+                hlt
+            ";
+        let outcome = run(
+            "uart",
+            &[("echo.s", src.as_bytes())],
+            r#"
+            [[case]]
+            name = "echo"
+            src = "echo.s"
+            uart_input = "hi\n"
+            cycles = 1000000
+
+            [case.expect]
+            status = 0
+            output = "hi\n"
+            "#,
+        );
+        // Ensure input is echoed through the UART
+        assert!(outcome.passed(), "{:?}", outcome.diffs);
+    }
+}
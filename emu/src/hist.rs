@@ -0,0 +1,130 @@
+//! Execution history.
+//!
+//! To support reverse execution, the processor can keep an undo log holding
+//! a [`Record`] for each of its most recent instructions. Each record holds
+//! the state from before the instruction, along with any writes it made to
+//! RAM, so that it can be undone. The system control block and devices are
+//! only saved by instructions which change them.
+//!
+//! Side effects on the host, such as console output from system calls, are
+//! not undone.
+
+use std::collections::VecDeque;
+
+use crate::bus::{Bus, Map, Part};
+use crate::prof::Frame;
+use crate::{uarch, BANKSIZE};
+
+/// A write to memory made by an instruction.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Write {
+    /// Number of cycles executed before the write's instruction.
+    pub cycle: u64,
+    /// Address of the instruction which made the write.
+    pub pc: uarch,
+    pub addr: uarch,
+    pub old: uarch,
+    pub new: uarch,
+}
+
+/// State of the processor from before an instruction.
+#[derive(Debug)]
+pub struct Record {
+    pub regs: [uarch; BANKSIZE],
+    pub sr: uarch,
+    pub halted: bool,
+    pub cycles: u64,
+    pub instrs: u64,
+    /// System control block, if the instruction changed it.
+    pub ctl: Option<Vec<u8>>,
+    /// Devices changed by the instruction, as `(line, ticks, state)`, where
+    /// `ticks` is the number of cycles the state had already been advanced.
+    pub devices: Vec<(usize, u64, Vec<u8>)>,
+    /// Address of the instruction executed.
+    pub pc: uarch,
+    /// Writes to RAM made by the instruction, as `(addr, old, new)`.
+    pub writes: Vec<(uarch, uarch, uarch)>,
+    /// Function the instruction returned from, while profiling.
+    pub returned: Option<Frame>,
+}
+
+impl Record {
+    /// Saves a part of the map before the instruction first changes it.
+    pub fn save(&mut self, bus: &Map, part: Part, ticks: u64) {
+        match part {
+            Part::Ctl if self.ctl.is_none() => self.ctl = Some(bus.ctl.save()),
+            Part::Device(line) if self.devices.iter().all(|&(saved, ..)| saved != line) => {
+                self.devices.push((line, ticks, bus.save_device(line)));
+            }
+            _ => (),
+        }
+    }
+}
+
+/// A bounded log of records, oldest first.
+#[derive(Debug, Default)]
+pub struct History {
+    limit: usize,
+    records: VecDeque<Record>,
+}
+
+impl History {
+    /// Maximum number of records kept; zero disables recording.
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Changes the number of records kept, discarding the oldest if needed.
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        while self.records.len() > limit {
+            self.records.pop_front();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Appends a record, discarding the oldest if full.
+    pub fn push(&mut self, record: Record) {
+        if self.limit == 0 {
+            return;
+        }
+        if self.records.len() == self.limit {
+            self.records.pop_front();
+        }
+        self.records.push_back(record);
+    }
+
+    /// Removes the most recent record.
+    pub fn pop(&mut self) -> Option<Record> {
+        self.records.pop_back()
+    }
+
+    /// Most recent record, belonging to the instruction being executed.
+    pub fn last_mut(&mut self) -> Option<&mut Record> {
+        self.records.back_mut()
+    }
+
+    pub fn clear(&mut self) {
+        self.records.clear();
+    }
+
+    /// Finds the most recent recorded write to an address.
+    pub fn last_write(&self, addr: uarch) -> Option<Write> {
+        self.records.iter().rev().find_map(|rec| {
+            rec.writes
+                .iter()
+                .rev()
+                .find(|&&(at, _, _)| at == addr)
+                .map(|&(addr, old, new)| Write {
+                    cycle: rec.cycles,
+                    pc: rec.pc,
+                    addr,
+                    old,
+                    new,
+                })
+        })
+    }
+}
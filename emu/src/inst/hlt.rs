@@ -0,0 +1,11 @@
+use isa::inst::hlt::Hlt;
+
+use super::Execute;
+use crate::Processor;
+
+impl Execute for Hlt {
+    fn execute(&self, proc: &mut Processor) {
+        // Stop the processor
+        proc.halted = true;
+    }
+}
@@ -0,0 +1,13 @@
+use isa::inst::iff::Iff;
+
+use super::Execute;
+use crate::{uarch, Processor, WORDSIZE};
+
+impl Execute for Iff {
+    fn execute(&self, proc: &mut Processor) {
+        // Skip the next instruction if the condition fails
+        if !self.cond.eval(*proc.sr) {
+            *proc.regs[15] += WORDSIZE as uarch;
+        }
+    }
+}
@@ -0,0 +1,11 @@
+use isa::inst::rti::Rti;
+
+use super::Execute;
+use crate::Processor;
+
+impl Execute for Rti {
+    fn execute(&self, proc: &mut Processor) {
+        // Restore the interrupted state
+        proc.rti();
+    }
+}
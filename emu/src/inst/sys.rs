@@ -0,0 +1,25 @@
+use isa::inst::sys::Sys;
+
+use super::Execute;
+use crate::sys::Control;
+use crate::Processor;
+
+impl Execute for Sys {
+    fn execute(&self, proc: &mut Processor) {
+        // Extract arguments
+        let mut args = [0; 4];
+        for (arg, reg) in args.iter_mut().zip(proc.regs.iter()) {
+            *arg = **reg;
+        }
+        // Hand off to the host
+        let ctl = proc.sys.sys(&mut args, proc.cycles);
+        // Set results
+        for (reg, arg) in proc.regs.iter_mut().zip(args) {
+            **reg = arg;
+        }
+        if let Control::Exit(code) = ctl {
+            *proc.regs[0] = code;
+            proc.halted = true;
+        }
+    }
+}
@@ -1,49 +1,131 @@
 //! # Emulator
 //!
 //! `emu` is an emulator for the KAP-16 microprocessor.
+//!
+//! It can also be embedded as a library:
+//!
+//! ```
+//! use emu::{Emulator, StopReason};
+//!
+//! let mut e = Emulator::new();
+//! // .word 0x0002; mov a0, 0x2a; hlt
+//! e.load_bytes(&[0x02, 0x00, 0xaa, 0x70, 0x00, 0x0c]).unwrap();
+//! assert_eq!(e.run(), StopReason::Halt(0x2a));
+//! ```
 
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fmt::{self, Display};
 use std::fs::File;
 use std::io::{self, Read};
-use std::mem;
+use std::ops::Range;
 use std::path::Path;
 
 use log::{debug, error, info, trace, warn};
 
+use isa::{iarch, WORDSIZE};
+
+pub mod bus;
+pub mod clock;
+mod ctl;
+pub mod dbg;
+pub mod gdb;
+pub mod harness;
+mod hist;
 mod inst;
 mod proc;
+pub mod prof;
 mod ram;
 mod reg;
-mod util;
+mod snap;
+mod stop;
+pub mod sym;
+pub mod sys;
+pub mod timer;
+pub mod timing;
+pub mod trace;
+pub mod uart;
+mod watch;
 
-use self::proc::Processor;
+pub use isa::{uarch, Encoding, Instruction};
 
-#[allow(non_camel_case_types)]
-type iarch = i16;
-#[allow(non_camel_case_types)]
-type uarch = u16;
+use self::bus::{Bus, MapError};
+pub use self::clock::Clock;
+pub use self::hist::Write;
+pub use self::proc::Flag;
+use self::proc::Processor;
+pub use self::prof::Profiler;
+pub use self::snap::{Snapshot, SnapshotError};
+pub use self::stop::{Fault, FaultKind, StopReason};
+pub use self::sym::Symbols;
+pub use self::sys::{Console, Control, SysHandler};
+pub use self::timer::Timer;
+pub use self::timing::Timing;
+pub use self::trace::Tracer;
+pub use self::uart::Uart;
+pub use self::watch::{Watch, Watchpoint};
 
 const BANKSIZE: usize = 0x10;
 const RAMSIZE: usize = 0x4000;
-const WORDSIZE: usize = mem::size_of::<uarch>();
+/// Address of the reset vector, which holds the program's entry point.
+const RESET: uarch = 0x0000;
 
 #[derive(Default)]
 pub struct Emulator {
     proc: Processor,
+    breaks: BTreeSet<uarch>,
+    prof: Option<Profiler>,
+    trace: Option<Tracer>,
+    /// Whether to stop once the PC stops advancing.
+    idle: bool,
 }
 
 impl Emulator {
     pub fn new() -> Self {
+        let mut proc = Processor::new();
+        proc.bus
+            .attach(timer::RANGE, Box::new(Timer::new()))
+            .expect("timer range should be free");
+        proc.bus
+            .attach(clock::RANGE, Box::new(Clock::new()))
+            .expect("clock range should be free");
+        proc.bus
+            .attach(uart::RANGE, Box::new(Uart::new()))
+            .expect("UART range should be free");
         Self {
-            proc: Processor::new(),
+            proc,
+            ..Default::default()
         }
     }
 
+    pub fn set_encoding(&mut self, enc: Encoding) {
+        self.proc.enc = enc;
+    }
+
+    /// Replaces the cost of each instruction.
+    pub fn set_timing(&mut self, timing: Timing) {
+        self.proc.timing = timing;
+    }
+
+    /// Replaces the handler invoked on `sys`.
+    pub fn set_sys_handler(&mut self, handler: impl SysHandler + 'static) {
+        self.proc.sys = Box::new(handler);
+    }
+
+    /// Replaces the UART, such as with one connected to a port.
+    pub fn set_uart(&mut self, uart: Uart) {
+        self.proc
+            .bus
+            .replace(uart::RANGE, Box::new(uart))
+            .expect("UART should be attached");
+    }
+
     pub fn load(&mut self, file: &Path) -> io::Result<()> {
         // Open the ROM file
         let mut f = File::open(file)?;
 
         // Read its contents into memory
-        let buf = &mut self.proc.ram.0;
+        let buf = &mut self.proc.bus.ram.0;
         let read = f.read(buf)?;
 
         // Error checking
@@ -63,18 +145,701 @@ impl Emulator {
             );
         }
 
+        // Start execution at the entry point
+        self.reset();
+
+        Ok(())
+    }
+
+    /// Loads an image into memory, then resets the processor.
+    pub fn load_bytes(&mut self, bytes: &[u8]) -> Result<(), LoadError> {
+        self.load_at(0, bytes)?;
+        self.reset();
         Ok(())
     }
 
-    pub fn main(&mut self) {
+    /// Copies bytes into memory starting at an address.
+    pub fn load_at(&mut self, addr: usize, bytes: &[u8]) -> Result<(), LoadError> {
+        let buf = &mut self.proc.bus.ram.0;
+        addr.checked_add(bytes.len())
+            .and_then(|end| buf.get_mut(addr..end))
+            .ok_or(LoadError::OutOfRange(addr, bytes.len()))?
+            .copy_from_slice(bytes);
+        Ok(())
+    }
+
+    /// Starts execution at the entry point held by the reset vector.
+    ///
+    /// Images in the legacy v0 encoding have no reset vector, so are entered
+    /// at their start instead.
+    pub fn reset(&mut self) {
+        *self.proc.regs[15] = match self.proc.enc {
+            Encoding::V0 => RESET,
+            _ => self.proc.bus.ram[RESET],
+        };
+        self.proc.halted = false;
+    }
+
+    /// Runs the processor until it stops.
+    pub fn run(&mut self) -> StopReason {
+        self.run_until(|_| false)
+    }
+
+    /// Runs the processor for at most a number of cycles.
+    pub fn run_for(&mut self, cycles: u64) -> StopReason {
+        let end = self.proc.cycles.saturating_add(cycles);
+        match self.run_until(|emu| emu.proc.cycles >= end) {
+            StopReason::Condition => StopReason::CycleLimit,
+            reason => reason,
+        }
+    }
+
+    /// Runs the processor until a predicate holds after an instruction.
+    ///
+    /// Execution also stops upon halting, faulting, hitting a watchpoint,
+    /// idling if enabled, or reaching a breakpoint.
+    /// The instruction at the current PC is always executed, even if it is a
+    /// breakpoint, so that execution can resume from one.
+    pub fn run_until(&mut self, mut pred: impl FnMut(&Self) -> bool) -> StopReason {
+        loop {
+            if let Err(reason) = self.step() {
+                return reason;
+            }
+            if let Some(reason) = self.proc.watched.take() {
+                return reason;
+            }
+            if self.proc.halted {
+                return StopReason::Halt(*self.proc.regs[0]);
+            }
+            if self.idle && self.pc() == self.proc.last {
+                return StopReason::Idle(self.pc());
+            }
+            if pred(self) {
+                return StopReason::Condition;
+            }
+            if self.breaks.contains(&self.pc()) {
+                return StopReason::Breakpoint(self.pc());
+            }
+        }
+    }
+
+    /// Executes a single instruction, returning it.
+    ///
+    /// Returns `None` if the instruction faulted and the program's fault
+    /// handler was entered instead. Nothing is executed once the processor has
+    /// halted or if it would fault without a handler.
+    pub fn step(&mut self) -> Result<Option<Instruction>, StopReason> {
+        if self.proc.halted {
+            return Err(StopReason::Halt(*self.proc.regs[0]));
+        }
+        // Forget any watchpoint hit by the previous instruction
+        self.proc.watched = None;
+        let (from, sr, start) = (self.pc(), self.sr(), self.proc.cycles);
+        let regs = self.trace.is_some().then(|| *self.proc.regs);
+        let instr = self.proc.cycle().map_err(StopReason::Fault)?;
+        if let Some(prof) = &mut self.prof {
+            // Remember returns, so that undoing them re-enters the function
+            let returned = prof.sample(from, sr, start, instr, &self.proc);
+            if let (Some(frame), Some(rec)) = (returned, self.proc.hist.last_mut()) {
+                rec.returned = Some(frame);
+            }
+        }
+        if let (Some(tracer), Some(regs)) = (&mut self.trace, regs) {
+            tracer.record(&trace::Record::capture(&self.proc, &regs, sr, start));
+        }
+        match instr {
+            Some(instr) => info!("{}", instr),
+            None => info!("Entered fault handler at {:#06x}", self.pc()),
+        }
+        debug!("{}", self.proc);
+        trace!("{}", self.proc.bus);
+        Ok(instr)
+    }
+
+    /// Starts or stops profiling execution.
+    ///
+    /// Stopping discards the profile. Undoing instructions or restoring a
+    /// snapshot does not remove them from the profile, but leaves any
+    /// functions entered since. Undoing a return re-enters its function.
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.prof = enabled.then(|| self.prof.take().unwrap_or_default());
+    }
+
+    /// Profile of the instructions executed since profiling started.
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.prof.as_ref()
+    }
+
+    /// Sets whether to stop once an instruction leaves the PC unchanged, such
+    /// as a branch to itself.
+    ///
+    /// Programs often idle this way once finished. Disabled by default, as
+    /// such loops may also wait for an interrupt.
+    pub fn set_stop_on_idle(&mut self, enabled: bool) {
+        self.idle = enabled;
+    }
+
+    /// Starts or stops tracing each instruction executed.
+    ///
+    /// Returns the previous tracer, if any, so that it can be finished.
+    pub fn set_tracer(&mut self, tracer: Option<Tracer>) -> Option<Tracer> {
+        std::mem::replace(&mut self.trace, tracer)
+    }
+
+    /// Sets the number of instructions recorded for reverse execution.
+    ///
+    /// Recording is disabled by default, or when set to zero.
+    pub fn set_history(&mut self, len: usize) {
+        self.proc.hist.set_limit(len);
+    }
+
+    /// Number of instructions which can currently be undone.
+    pub fn history(&self) -> usize {
+        self.proc.hist.len()
+    }
+
+    /// Undoes the most recently executed instruction.
+    ///
+    /// Returns `false` if there is no recorded history to undo.
+    pub fn step_back(&mut self) -> bool {
+        let Some(rec) = self.proc.undo() else {
+            return false;
+        };
+        if let Some(prof) = &mut self.prof {
+            prof.rewind(self.proc.cycles, rec.returned);
+        }
+        true
+    }
+
+    /// Runs the processor backwards until reaching a breakpoint.
+    ///
+    /// Execution also stops once the recorded history is exhausted. At least
+    /// one instruction is always undone, so that execution can resume from a
+    /// breakpoint.
+    pub fn run_back(&mut self) -> StopReason {
         loop {
-            let instr = self.proc.cycle();
-            info!("{}", instr);
-            debug!("{}", self.proc);
-            trace!("{}", self.proc.ram);
+            if !self.step_back() {
+                return StopReason::HistoryExhausted;
+            }
+            if self.breaks.contains(&self.pc()) {
+                return StopReason::Breakpoint(self.pc());
+            }
+        }
+    }
+
+    /// Finds the most recent recorded write to an address.
+    pub fn last_write(&self, addr: uarch) -> Option<Write> {
+        self.proc.hist.last_write(addr)
+    }
+
+    /// Stops execution before the instruction at an address.
+    pub fn set_breakpoint(&mut self, addr: uarch) {
+        self.breaks.insert(addr);
+    }
+
+    /// Removes a breakpoint, returning whether it was set.
+    pub fn clear_breakpoint(&mut self, addr: uarch) -> bool {
+        self.breaks.remove(&addr)
+    }
+
+    /// Stops execution after an instruction accesses a range of addresses.
+    pub fn set_watchpoint(&mut self, range: Range<usize>, kind: Watch) {
+        self.proc.watches.push(Watchpoint { range, kind });
+    }
+
+    /// Watchpoints currently set.
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.proc.watches
+    }
+
+    /// Removes a watchpoint, returning whether it was set.
+    pub fn clear_watchpoint(&mut self, range: Range<usize>, kind: Watch) -> bool {
+        let wp = Watchpoint { range, kind };
+        match self.proc.watches.iter().position(|other| *other == wp) {
+            Some(idx) => {
+                self.proc.watches.remove(idx);
+                true
+            }
+            None => false,
         }
     }
+
+    pub fn encoding(&self) -> Encoding {
+        self.proc.enc
+    }
+
+    pub fn halted(&self) -> bool {
+        self.proc.halted
+    }
+
+    /// Number of cycles executed, as weighted by the timing table.
+    pub fn cycles(&self) -> u64 {
+        self.proc.cycles
+    }
+
+    /// Number of instructions executed.
+    pub fn instructions(&self) -> u64 {
+        self.proc.instrs
+    }
+
+    /// Reads a general purpose register.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `reg` is not a register number.
+    pub fn reg(&self, reg: uarch) -> uarch {
+        *self.proc.regs[reg]
+    }
+
+    /// Writes a general purpose register.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `reg` is not a register number.
+    pub fn set_reg(&mut self, reg: uarch, value: uarch) {
+        *self.proc.regs[reg] = value;
+    }
+
+    pub fn pc(&self) -> uarch {
+        *self.proc.regs[15]
+    }
+
+    pub fn set_pc(&mut self, value: uarch) {
+        *self.proc.regs[15] = value;
+    }
+
+    /// Reads the status register.
+    pub fn sr(&self) -> uarch {
+        *self.proc.sr
+    }
+
+    /// Writes the status register.
+    pub fn set_sr(&mut self, value: uarch) {
+        *self.proc.sr = value;
+    }
+
+    /// Reads a status register flag.
+    pub fn flag(&self, flag: Flag) -> bool {
+        *self.proc.sr & flag.bit() != 0
+    }
+
+    /// Writes a status register flag.
+    pub fn set_flag(&mut self, flag: Flag, value: bool) {
+        match value {
+            true => *self.proc.sr |= flag.bit(),
+            false => *self.proc.sr &= !flag.bit(),
+        }
+    }
+
+    /// Reads the word at an address without side effects.
+    ///
+    /// Returns `None` if the address cannot be inspected.
+    pub fn read(&self, addr: uarch) -> Option<uarch> {
+        self.proc.bus.peek(addr)
+    }
+
+    /// Writes the word at an address, as the processor would.
+    ///
+    /// Returns `None` if the write fails.
+    pub fn write(&mut self, addr: uarch, word: uarch) -> Option<()> {
+        self.proc.bus.write(addr, word)
+    }
+
+    /// Write-protects the first `len` bytes of memory, where the program image
+    /// is loaded.
+    pub fn protect(&mut self, len: usize) {
+        self.proc.bus.protect(len);
+    }
+
+    /// Captures the state of the machine.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot::capture(&self.proc)
+    }
+
+    /// Restores the state of the machine from a snapshot.
+    ///
+    /// The same devices must be attached as when the snapshot was taken. Upon
+    /// failure, the machine is left unchanged.
+    pub fn restore(&mut self, snap: &Snapshot) -> Result<(), SnapshotError> {
+        let backup = self.snapshot();
+        snap.apply(&mut self.proc).inspect_err(|_| {
+            backup
+                .apply(&mut self.proc)
+                .expect("backup should match attached devices");
+        })?;
+        // Recorded history no longer leads to this state
+        self.proc.hist.clear();
+        if let Some(prof) = &mut self.prof {
+            prof.rewind(self.proc.cycles, None);
+        }
+        Ok(())
+    }
+
+    /// Attaches a device to the bus at a range of addresses.
+    ///
+    /// Returns the interrupt line the device requests interrupts on. Lines 0
+    /// through 2 belong to the timer, cycle counter and UART.
+    pub fn attach(
+        &mut self,
+        range: Range<usize>,
+        dev: impl Bus + 'static,
+    ) -> Result<usize, MapError> {
+        self.proc.bus.attach(range, Box::new(dev))
+    }
+}
+
+impl Display for Emulator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.proc)
+    }
 }
 
+#[derive(Debug)]
+pub enum LoadError {
+    OutOfRange(usize, usize),
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::OutOfRange(addr, len) => {
+                    format!("Cannot fit {} bytes at {:#06x} in memory", len, addr)
+                }
+            }
+        )
+    }
+}
+
+impl Error for LoadError {}
+
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hlt() {
+        let mut e = Emulator::new();
+        // mov r0, 0x2a; hlt
+        e.proc.bus.ram[0x0000] = 0x70aa;
+        e.proc.bus.ram[0x0002] = 0x0c00;
+        assert_eq!(e.run(), StopReason::Halt(0x002a));
+        assert!(e.proc.halted);
+        assert_eq!(*e.proc.regs[15], 0x0004);
+    }
+
+    #[test]
+    fn legacy() {
+        let mut e = Emulator::new();
+        e.set_encoding(Encoding::V0);
+        e.set_stop_on_idle(true);
+        // mov r0, 0x2a; add r0, 0x1; goto .
+        e.load_bytes(&[0xaa, 0xa0, 0x81, 0xc0, 0xff, 0xf0]).unwrap();
+        // Ensure images without a reset vector are entered at their start
+        assert_eq!(e.pc(), 0x0000);
+        assert_eq!(e.run(), StopReason::Idle(0x0004));
+        assert_eq!(e.reg(0), 0x002b);
+    }
+
+    #[test]
+    fn iff() {
+        let mut e = Emulator::new();
+        // mov r0, 0x1; cmp r0, 0x1
+        e.proc.bus.ram[0x0000] = 0x7081;
+        e.proc.bus.ram[0x0002] = 0x8081;
+        // ifne; mov r0, 0x2
+        e.proc.bus.ram[0x0004] = 0x0e03;
+        e.proc.bus.ram[0x0006] = 0x7082;
+        // ifeq; add r0, 0x4
+        e.proc.bus.ram[0x0008] = 0x0e02;
+        e.proc.bus.ram[0x000a] = 0xc084;
+        // hlt
+        e.proc.bus.ram[0x000c] = 0x0c00;
+        assert_eq!(e.run(), StopReason::Halt(0x0005));
+    }
+
+    #[test]
+    fn run() {
+        let mut e = Emulator::new();
+        // .word 0x0002; loop: add r0, 0x1; goto loop
+        e.load_bytes(&[0x02, 0x00, 0x81, 0xc0, 0xfe, 0x00]).unwrap();
+        assert_eq!(e.pc(), 0x0002);
+        assert_eq!(e.run_for(5), StopReason::CycleLimit);
+        assert_eq!(e.reg(0), 0x0002);
+        assert_eq!(e.run_until(|e| e.reg(0) == 0x0010), StopReason::Condition);
+        // Ensure breakpoints stop execution, and can be resumed from
+        e.set_breakpoint(0x0004);
+        assert_eq!(e.run(), StopReason::Breakpoint(0x0004));
+        assert_eq!(e.run(), StopReason::Breakpoint(0x0004));
+        assert_eq!(e.reg(0), 0x0012);
+        // Ensure faults are reported
+        e.set_pc(0x0003);
+        assert!(matches!(e.step(), Err(StopReason::Fault(_))));
+        assert!(e.load_at(RAMSIZE - 1, &[0, 0]).is_err());
+        // Ensure idle loops stop execution once enabled
+        let mut e = Emulator::new();
+        // .word 0x0002; mov r0, 0x7; goto .
+        e.load_bytes(&[0x02, 0x00, 0x87, 0x70, 0xff, 0x00]).unwrap();
+        assert_eq!(e.run_for(10), StopReason::CycleLimit);
+        e.set_stop_on_idle(true);
+        assert_eq!(e.run(), StopReason::Idle(0x0004));
+        assert_eq!(e.reg(0), 0x0007);
+    }
+
+    #[test]
+    fn fault() {
+        let mut e = Emulator::new();
+        // .word 0x0002; .word 0x0e0f
+        e.load_bytes(&[0x02, 0x00, 0x0f, 0x0e]).unwrap();
+        let fault = Fault {
+            pc: 0x0002,
+            kind: FaultKind::Undefined(0x0e0f),
+        };
+        assert_eq!(e.run(), StopReason::Fault(fault));
+        assert_eq!(e.pc(), 0x0002);
+        // Install a fault handler which halts
+        e.write(0x0100, 0x0020).unwrap();
+        e.write(0x0020, 0x0c00).unwrap();
+        e.write(0xfff0, 0x0100).unwrap();
+        assert_eq!(e.step(), Ok(None));
+        assert_eq!(e.pc(), 0x0020);
+        assert_eq!(e.run(), StopReason::Halt(0x0000));
+        // Ensure the fault was recorded
+        assert_eq!(e.read(0xfff2), Some(0x0002));
+        assert_eq!(e.read(0xfff4), Some(0x0e0f));
+        assert_eq!(e.read(0xfff6), Some(0x0002));
+    }
+
+    #[test]
+    fn interrupt() {
+        let mut e = Emulator::new();
+        // .word 0x0002; loop: add r0, 0x1; goto loop
+        e.load_bytes(&[0x02, 0x00, 0x81, 0xc0, 0xfe, 0x00]).unwrap();
+        // handler: add r1, 0x1; str r3, r2; rti
+        e.write(0x0010, 0xc181).unwrap();
+        e.write(0x0012, 0x2302).unwrap();
+        e.write(0x0014, 0x0a00).unwrap();
+        e.set_reg(2, 0xfffc);
+        e.set_reg(3, 0x0001);
+        // Install the handler, then start the timer
+        e.write(0x0102, 0x0010).unwrap();
+        e.write(0xfff0, 0x0100).unwrap();
+        e.write(0xfffe, 0x0001).unwrap();
+        e.write(0xffe0, 0x0005).unwrap();
+        e.write(0xffe2, 0x0005).unwrap();
+        e.write(0xffe4, timer::ENABLE).unwrap();
+        // Ensure interrupts are ignored while disabled
+        assert_eq!(e.run_for(6), StopReason::CycleLimit);
+        assert_eq!(e.reg(1), 0x0000);
+        assert_eq!(e.read(0xfffc), Some(0x0001));
+        // Ensure the handler is entered with interrupts disabled
+        e.set_flag(Flag::Interrupt, true);
+        let pc = e.pc();
+        e.step().unwrap();
+        assert_eq!(e.pc(), 0x0012);
+        assert_eq!(e.reg(1), 0x0001);
+        assert!(!e.flag(Flag::Interrupt));
+        // Ensure the handler returns to where it interrupted
+        e.step().unwrap();
+        assert_eq!(e.read(0xfffc), Some(0x0000));
+        e.step().unwrap();
+        assert_eq!(e.pc(), pc);
+        assert!(e.flag(Flag::Interrupt));
+        // Ensure the timer keeps interrupting
+        assert_eq!(e.run_for(10), StopReason::CycleLimit);
+        assert_eq!(e.reg(1), 0x0003);
+    }
+
+    #[test]
+    fn protect() {
+        let mut e = Emulator::new();
+        // .word 0x0002; mov r1, 0x0; str r0, r1; hlt
+        let rom = [0x02, 0x00, 0x80, 0x71, 0x01, 0x20, 0x00, 0x0c];
+        e.load_bytes(&rom).unwrap();
+        e.protect(rom.len());
+        assert_eq!(
+            e.run(),
+            StopReason::Fault(Fault {
+                pc: 0x0004,
+                kind: FaultKind::Bus(0x0000)
+            })
+        );
+        // Ensure nothing was written
+        assert_eq!(e.read(0x0000), Some(0x0002));
+    }
+
+    #[test]
+    fn reverse() {
+        let mut e = Emulator::new();
+        e.set_history(4);
+        // .word 0x0002; mov r1, 0x20; loop: add r0, 0x1; str r0, r1; goto loop
+        e.load_bytes(&[0x02, 0x00, 0xa0, 0x71, 0x81, 0xc0, 0x01, 0x20, 0xfd, 0x00])
+            .unwrap();
+        assert_eq!(e.run_for(9), StopReason::CycleLimit);
+        assert_eq!(e.reg(0), 0x0002);
+        assert_eq!(e.history(), 4);
+        // Ensure writes can be found
+        let write = Write {
+            cycle: 7,
+            pc: 0x0006,
+            addr: 0x0020,
+            old: 0x0001,
+            new: 0x0002,
+        };
+        assert_eq!(e.last_write(0x0020), Some(write));
+        // Ensure instructions are undone, along with their writes
+        assert!(e.step_back());
+        assert_eq!(e.pc(), 0x0006);
+        assert_eq!(e.cycles(), 7);
+        assert_eq!(e.instructions(), 5);
+        assert_eq!(e.read(0x0020), Some(0x0001));
+        // Ensure reverse execution stops at breakpoints
+        e.set_breakpoint(0x0004);
+        assert_eq!(e.run_back(), StopReason::Breakpoint(0x0004));
+        assert_eq!(e.reg(0), 0x0001);
+        assert_eq!(e.run_back(), StopReason::HistoryExhausted);
+        assert_eq!(e.cycles(), 2);
+        assert!(!e.step_back());
+    }
+
+    #[test]
+    fn reverse_devices() {
+        let mut e = Emulator::new();
+        e.set_history(16);
+        // .word 0x0002; loop: add r0, 0x1; goto loop
+        e.load_bytes(&[0x02, 0x00, 0x81, 0xc0, 0xfe, 0x00]).unwrap();
+        // handler: str r3, r2; rti
+        e.write(0x0010, 0x2302).unwrap();
+        e.write(0x0012, 0x0a00).unwrap();
+        e.set_reg(2, 0xfffc);
+        e.set_reg(3, 0x0001);
+        e.write(0x0102, 0x0010).unwrap();
+        e.write(0xfff0, 0x0100).unwrap();
+        e.write(0xfffe, 0x0001).unwrap();
+        e.write(0xffe0, 0x0003).unwrap();
+        e.write(0xffe2, 0x0003).unwrap();
+        e.write(0xffe4, timer::ENABLE).unwrap();
+        e.set_flag(Flag::Interrupt, true);
+        // Timer, clock, and control registers
+        let state = |e: &Emulator| {
+            [0xffe0, 0xffe4, 0xffe8, 0xfff8, 0xfffa, 0xfffc]
+                .map(|addr| e.read(addr))
+                .to_vec()
+        };
+        let mut states = Vec::new();
+        for _ in 0..12 {
+            states.push((e.pc(), state(&e)));
+            e.step().unwrap();
+        }
+        // Ensure devices are restored, whether or not they were saved
+        while let Some(expected) = states.pop() {
+            assert!(e.step_back());
+            assert_eq!((e.pc(), state(&e)), expected);
+        }
+    }
+
+    #[test]
+    fn watchpoint() {
+        let mut e = Emulator::new();
+        // .word 0x0002; mov sp, 0x30; mov r0, 0x5; push r0; pop r1; hlt
+        let rom = [
+            0x02, 0x00, 0xb0, 0x7d, 0x85, 0x70, 0x40, 0x20, 0x40, 0x31, 0x00, 0x0c,
+        ];
+        e.load_bytes(&rom).unwrap();
+        e.set_watchpoint(0x0020..0x0030, Watch::Write);
+        e.set_watchpoint(0x002e..0x0030, Watch::Read);
+        // Ensure pushes and pops are watched
+        let hit = StopReason::Watchpoint {
+            addr: 0x002e,
+            old: 0x0000,
+            new: 0x0005,
+            pc: 0x0006,
+            access: Watch::Write,
+        };
+        assert_eq!(e.run(), hit);
+        assert_eq!(e.pc(), 0x0008);
+        let hit = StopReason::Watchpoint {
+            addr: 0x002e,
+            old: 0x0005,
+            new: 0x0005,
+            pc: 0x0008,
+            access: Watch::Read,
+        };
+        assert_eq!(e.run(), hit);
+        assert_eq!(e.reg(1), 0x0005);
+        assert_eq!(e.run(), StopReason::Halt(0x0005));
+        // Ensure watchpoints can be removed
+        e.reset();
+        assert!(e.clear_watchpoint(0x0020..0x0030, Watch::Write));
+        assert!(!e.clear_watchpoint(0x0020..0x0030, Watch::Write));
+        assert!(e.clear_watchpoint(0x002e..0x0030, Watch::Read));
+        e.set_watchpoint(0x0030..0x0040, Watch::Access);
+        assert_eq!(e.run(), StopReason::Halt(0x0005));
+    }
+
+    #[test]
+    fn snapshot() {
+        let mut e = Emulator::new();
+        // .word 0x0002; loop: add r0, 0x1; goto loop
+        e.load_bytes(&[0x02, 0x00, 0x81, 0xc0, 0xfe, 0x00]).unwrap();
+        e.write(0xffe0, 0x0100).unwrap();
+        e.write(0xffe4, timer::ENABLE).unwrap();
+        e.run_for(5);
+        let snap = Snapshot::from_bytes(&e.snapshot().to_bytes()).unwrap();
+        assert_eq!(snap.cycles(), 6);
+        // Ensure restoring rewinds the machine
+        e.run_for(5);
+        e.restore(&snap).unwrap();
+        assert_eq!(e.reg(0), 0x0002);
+        assert_eq!(e.cycles(), 6);
+        assert_eq!(e.read(0xffe0), Some(0x00fa));
+        // Ensure execution resumes identically
+        let mut f = Emulator::new();
+        f.restore(&snap).unwrap();
+        e.run_for(7);
+        f.run_for(7);
+        assert_eq!(f.snapshot(), e.snapshot());
+        // Ensure mismatched devices are rejected
+        let mut g = Emulator::new();
+        g.attach(0xff00..0xff02, Timer::new()).unwrap();
+        assert!(matches!(g.restore(&snap), Err(SnapshotError::Devices)));
+        assert_eq!(g.pc(), 0x0000);
+    }
+
+    #[test]
+    fn timing() {
+        let mut e = Emulator::new();
+        // .word 0x0002; mul r0, 0x1; ldr r1, r2; hlt
+        e.load_bytes(&[0x02, 0x00, 0x81, 0x60, 0x02, 0x31, 0x00, 0x0c])
+            .unwrap();
+        e.set_reg(2, clock::RANGE.start as uarch);
+        assert_eq!(e.run(), StopReason::Halt(0x0000));
+        assert_eq!(e.cycles(), 6);
+        assert_eq!(e.instructions(), 3);
+        // Ensure the counter includes the load reading it
+        assert_eq!(e.reg(1), 0x0005);
+        assert_eq!(e.read(0xffe8), Some(0x0006));
+        // Ensure costs can be changed
+        let mut e = Emulator::new();
+        e.set_timing("mul 8".parse().unwrap());
+        e.load_bytes(&[0x02, 0x00, 0x81, 0x60, 0x00, 0x0c]).unwrap();
+        e.run();
+        assert_eq!(e.cycles(), 9);
+    }
+
+    #[test]
+    fn sys() {
+        let mut e = Emulator::new();
+        e.set_sys_handler(Console::new(io::empty(), io::sink()));
+        // mov r0, 0x0; mov r1, 0x7; sys
+        e.proc.bus.ram[0x0000] = 0x7080;
+        e.proc.bus.ram[0x0002] = 0x7187;
+        e.proc.bus.ram[0x0004] = 0x0800;
+        // hlt
+        e.proc.bus.ram[0x0006] = 0x0c00;
+        assert_eq!(e.run(), StopReason::Halt(0x0007));
+        assert_eq!(*e.proc.regs[15], 0x0006);
+    }
+}
@@ -1,10 +1,19 @@
-use std::path::PathBuf;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 use std::process;
 
-use clap::{Parser, ValueHint};
-use emu::Emulator;
+use clap::{ArgEnum, Parser, ValueHint};
+use emu::dbg::Debugger;
+use emu::gdb::{self, Stub};
+use emu::trace::{Format, Tracer};
+use emu::{Emulator, Snapshot, StopReason, Symbols, Timing, Uart, Watch};
 use env_logger as logger;
-use log::error;
+use isa::{reg, uarch, Encoding, WORDSIZE};
+use log::{error, info};
+use serde_json::{Map, Value};
 
 fn main() {
     // Initialize logger
@@ -19,13 +28,272 @@ fn main() {
 
     // Instantiate an emulator
     let mut e = Emulator::new();
-    // Load the ROM into memory
-    e.load(&args.rom).unwrap_or_else(|err| {
-        error!("`{}`: {}", &args.rom.display(), err);
+    e.set_encoding(args.encoding);
+    // Use a custom timing table if requested
+    if let Some(path) = &args.timing {
+        let timing = Timing::load(path).unwrap_or_else(|err| {
+            error!("`{}`: {}", path.display(), err);
+            process::exit(1)
+        });
+        e.set_timing(timing);
+    }
+    // Connect the UART to the requested port, leaving stdin to the debugger
+    let port = match (args.uart.as_deref(), args.debug) {
+        (Some("stdio"), true) => {
+            error!("The UART cannot use stdio under the debugger");
+            process::exit(1)
+        }
+        (Some(port), _) => port,
+        (None, true) => "none",
+        (None, false) => "stdio",
+    };
+    let uart = Uart::open(port).unwrap_or_else(|err| {
+        error!("`{}`: {}", port, err);
         process::exit(1)
     });
+    e.set_uart(uart);
+    // Load the ROM into memory
+    if let Some(rom) = &args.rom {
+        e.load(rom).unwrap_or_else(|err| {
+            error!("`{}`: {}", rom.display(), err);
+            process::exit(1)
+        });
+        // Write-protect the program image
+        if args.protect {
+            let len = fs::metadata(rom).map_or(0, |meta| meta.len() as usize);
+            e.protect(len);
+        }
+    }
+    // Resume from a saved state if requested
+    if let Some(path) = &args.load_state {
+        fs::read(path)
+            .map_err(Into::into)
+            .and_then(|bytes| Ok(Snapshot::from_bytes(&bytes)?))
+            .and_then(|snap| Ok(e.restore(&snap)?))
+            .unwrap_or_else(|err: Box<dyn Error>| {
+                error!("`{}`: {}", path.display(), err);
+                process::exit(1)
+            });
+    }
+    // Load symbols if requested
+    let symbols = match &args.map {
+        Some(path) => Symbols::load(path).unwrap_or_else(|err| {
+            error!("`{}`: {}", path.display(), err);
+            process::exit(1)
+        }),
+        None => Symbols::new(),
+    };
+    // Set watchpoints if requested
+    let watches = [
+        (&args.watch, Watch::Write),
+        (&args.rwatch, Watch::Read),
+        (&args.awatch, Watch::Access),
+    ];
+    for (specs, kind) in watches {
+        for spec in specs {
+            let range = watch_range(spec, &symbols).unwrap_or_else(|| {
+                error!("`{}`: Invalid watchpoint range", spec);
+                process::exit(1)
+            });
+            e.set_watchpoint(range, kind);
+        }
+    }
+    // Check the ranges of memory to dump
+    let dumps: Vec<_> = args
+        .dump_mem
+        .iter()
+        .map(|spec| {
+            dump_range(spec, &symbols).unwrap_or_else(|| {
+                error!("`{}`: Invalid memory range", spec);
+                process::exit(1)
+            })
+        })
+        .collect();
+    // Profile execution if requested
+    e.set_profiling(args.profile.is_some() || args.folded.is_some());
+    // Trace execution if requested
+    if let Some(path) = &args.trace {
+        let tracer = Format::from_path(path)
+            .map_err(Into::into)
+            .and_then(|format| Ok(Tracer::new(BufWriter::new(File::create(path)?), format)))
+            .unwrap_or_else(|err: Box<dyn Error>| {
+                error!("`{}`: {}", path.display(), err);
+                process::exit(1)
+            });
+        e.set_tracer(Some(tracer));
+    }
     // Run the emulator
-    e.main();
+    let status = run(&mut e, &args, &symbols);
+    // Report how long it ran for
+    eprintln!(
+        "Executed {} instructions in {} cycles",
+        e.instructions(),
+        e.cycles()
+    );
+    // Dump the final state if requested
+    if args.dump_regs || !dumps.is_empty() {
+        dump(&e, args.dump_regs, &dumps, args.dump_format);
+    }
+    // Finish the trace if requested
+    if let (Some(path), Some(tracer)) = (&args.trace, e.set_tracer(None)) {
+        tracer.finish().unwrap_or_else(|err| {
+            error!("`{}`: {}", path.display(), err);
+            process::exit(1)
+        });
+    }
+    // Save the final state if requested
+    if let Some(path) = &args.save_state {
+        fs::write(path, e.snapshot().to_bytes()).unwrap_or_else(|err| {
+            error!("`{}`: {}", path.display(), err);
+            process::exit(1)
+        });
+    }
+    // Write the profile if requested
+    if let Some(prof) = e.profiler() {
+        if let Some(path) = &args.profile {
+            write_report(path, |out| prof.report(&symbols, out));
+        }
+        if let Some(path) = &args.folded {
+            write_report(path, |out| prof.write_folded(&symbols, out));
+        }
+    }
+    process::exit(status);
+}
+
+/// Parses a watchpoint range of the form `LOC[:LEN]`, where `LEN` defaults to
+/// a single word.
+fn watch_range(spec: &str, symbols: &Symbols) -> Option<Range<usize>> {
+    let (loc, len) = match spec.split_once(':') {
+        Some((loc, len)) => (loc, symbols.resolve(len)? as usize),
+        None => (spec, WORDSIZE),
+    };
+    let start = symbols.resolve(loc)? as usize;
+    Some(start..start + len)
+}
+
+/// Parses a range of memory to dump of the form `START:LEN`, where `LEN` is a
+/// number of words.
+fn dump_range(spec: &str, symbols: &Symbols) -> Option<(uarch, usize)> {
+    let (start, len) = spec.split_once(':')?;
+    Some((symbols.resolve(start)?, symbols.resolve(len)? as usize))
+}
+
+/// Prints the registers and ranges of memory requested.
+fn dump(e: &Emulator, regs: bool, ranges: &[(uarch, usize)], format: DumpFormat) {
+    const ROWSIZE: usize = 8;
+    let words = |start: uarch, len: usize| {
+        (0..len).map(move |idx| e.read(start.wrapping_add((idx * WORDSIZE) as uarch)))
+    };
+    match format {
+        DumpFormat::Text => {
+            if regs {
+                println!("{}", e);
+            }
+            for &(start, len) in ranges {
+                let words: Vec<_> = words(start, len).collect();
+                for (idx, row) in words.chunks(ROWSIZE).enumerate() {
+                    print!("{:#06x}:", start as usize + idx * ROWSIZE * WORDSIZE);
+                    for word in row {
+                        match word {
+                            Some(word) => print!(" {:04x}", word),
+                            None => print!(" ????"),
+                        }
+                    }
+                    println!();
+                }
+            }
+        }
+        DumpFormat::Json => {
+            // Use the same layout as test cases, with unreadable words as null
+            let mut dump = Map::new();
+            if regs {
+                let regs: Map<_, _> = (0..reg::COUNT as uarch)
+                    .map(|idx| (reg::name(idx).to_string(), e.reg(idx).into()))
+                    .collect();
+                dump.insert("regs".to_string(), regs.into());
+                dump.insert("sr".to_string(), e.sr().into());
+            }
+            if !ranges.is_empty() {
+                let mem: Map<_, _> = ranges
+                    .iter()
+                    .map(|&(start, len)| {
+                        let words: Vec<_> = words(start, len).collect();
+                        (format!("{:#06x}", start), words.into())
+                    })
+                    .collect();
+                dump.insert("mem".to_string(), mem.into());
+            }
+            println!("{}", Value::from(dump));
+        }
+    }
+}
+
+/// Writes a report to a file, exiting upon failure.
+fn write_report(path: &Path, report: impl FnOnce(&mut BufWriter<File>) -> io::Result<()>) {
+    File::create(path)
+        .map(BufWriter::new)
+        .and_then(|mut out| {
+            report(&mut out)?;
+            out.flush()
+        })
+        .unwrap_or_else(|err| {
+            error!("`{}`: {}", path.display(), err);
+            process::exit(1)
+        });
+}
+
+/// Runs the emulator as requested, returning the exit status.
+fn run(e: &mut Emulator, args: &Args, symbols: &Symbols) -> i32 {
+    // Run under the debugger if requested
+    if args.debug {
+        e.set_history(args.history);
+        let mut dbg = Debugger::default();
+        dbg.set_symbols(symbols.clone());
+        dbg.run(e).unwrap_or_else(|err| {
+            error!("{}", err);
+            process::exit(1)
+        });
+        return match e.halted() {
+            true => e.reg(0) as i32,
+            false => 0,
+        };
+    }
+    // Serve a remote debugger if requested
+    if let Some(target) = &args.gdb {
+        e.set_history(args.history);
+        gdb::accept(target)
+            .and_then(|conn| Stub::new(conn).serve(e))
+            .unwrap_or_else(|err| {
+                error!("`{}`: {}", target, err);
+                process::exit(1)
+            });
+        return match e.halted() {
+            true => e.reg(0) as i32,
+            false => 0,
+        };
+    }
+    // Run the emulator, within a cycle budget if requested
+    e.set_stop_on_idle(args.stop_on_idle);
+    let reason = match args.max_cycles {
+        Some(cycles) => e.run_for(cycles),
+        None => e.run(),
+    };
+    // Report the final state
+    match reason {
+        StopReason::Halt(status) => {
+            info!("{}:\n{}", reason, e);
+            status as i32
+        }
+        // Idle programs have finished, so exit as if halted
+        StopReason::Idle(_) => {
+            info!("{}:\n{}", reason, e);
+            e.reg(0) as i32
+        }
+        reason => {
+            error!("{}:\n{}", reason, e);
+            1
+        }
+    }
 }
 
 /// Emulator for the KAP-16 processor.
@@ -35,10 +303,130 @@ struct Args {
     /// Input ROM file
     #[clap(parse(from_os_str))]
     #[clap(value_hint = ValueHint::FilePath)]
-    rom: PathBuf,
+    #[clap(required_unless_present = "load-state")]
+    rom: Option<PathBuf>,
+
+    /// Instruction encoding (v0, v1)
+    #[clap(long)]
+    #[clap(default_value = "v1")]
+    encoding: Encoding,
+
+    /// Instruction cycle costs to use instead of the defaults
+    #[clap(long)]
+    #[clap(parse(from_os_str))]
+    #[clap(value_hint = ValueHint::FilePath)]
+    timing: Option<PathBuf>,
+
+    /// Port to connect the UART to (stdio, file:PATH, pty, tcp:PORT, none)
+    /// [default: stdio, or none with --debug]
+    #[clap(long)]
+    #[clap(value_name = "PORT")]
+    uart: Option<String>,
+
+    /// Write-protect the loaded image
+    #[clap(long)]
+    protect: bool,
+
+    /// Run interactively under the debugger
+    #[clap(short, long)]
+    debug: bool,
+
+    /// Symbol map to use when debugging or profiling
+    #[clap(short, long)]
+    #[clap(parse(from_os_str))]
+    #[clap(value_hint = ValueHint::FilePath)]
+    map: Option<PathBuf>,
+
+    /// Serve a GDB remote on a local TCP port or Unix socket
+    #[clap(long)]
+    #[clap(value_name = "PORT|SOCKET")]
+    #[clap(conflicts_with = "debug")]
+    gdb: Option<String>,
+
+    /// Number of instructions to record for reverse debugging
+    #[clap(long)]
+    #[clap(value_name = "N")]
+    #[clap(default_value = "10000")]
+    history: usize,
+
+    /// Restore a machine state saved by `--save-state` before running
+    #[clap(long)]
+    #[clap(parse(from_os_str))]
+    #[clap(value_hint = ValueHint::FilePath)]
+    load_state: Option<PathBuf>,
+
+    /// Save the machine state to a file once stopped
+    #[clap(long)]
+    #[clap(parse(from_os_str))]
+    #[clap(value_hint = ValueHint::FilePath)]
+    save_state: Option<PathBuf>,
+
+    /// Stop once a store writes to a range of memory
+    #[clap(long)]
+    #[clap(value_name = "LOC[:LEN]")]
+    watch: Vec<String>,
+
+    /// Stop once a load reads from a range of memory
+    #[clap(long)]
+    #[clap(value_name = "LOC[:LEN]")]
+    rwatch: Vec<String>,
+
+    /// Stop once a load or store accesses a range of memory
+    #[clap(long)]
+    #[clap(value_name = "LOC[:LEN]")]
+    awatch: Vec<String>,
+
+    /// Write a profile of execution to a file
+    #[clap(long)]
+    #[clap(parse(from_os_str))]
+    #[clap(value_hint = ValueHint::FilePath)]
+    profile: Option<PathBuf>,
+
+    /// Write folded stacks of execution to a file, for flame graphs
+    #[clap(long)]
+    #[clap(parse(from_os_str))]
+    #[clap(value_hint = ValueHint::FilePath)]
+    folded: Option<PathBuf>,
+
+    /// Write a trace of each instruction executed to a `.jsonl` or `.csv` file
+    #[clap(long)]
+    #[clap(parse(from_os_str))]
+    #[clap(value_hint = ValueHint::FilePath)]
+    trace: Option<PathBuf>,
+
+    /// Stop after executing a number of cycles
+    #[clap(long)]
+    #[clap(value_name = "N")]
+    max_cycles: Option<u64>,
+
+    /// Stop once the PC stops advancing, as in a branch to itself
+    #[clap(long)]
+    stop_on_idle: bool,
+
+    /// Print the registers once stopped
+    #[clap(long)]
+    dump_regs: bool,
+
+    /// Print LEN words of memory from START once stopped
+    #[clap(long)]
+    #[clap(value_name = "START:LEN")]
+    dump_mem: Vec<String>,
+
+    /// Format to print the final state in
+    #[clap(long)]
+    #[clap(arg_enum)]
+    #[clap(default_value = "text")]
+    dump_format: DumpFormat,
 
     /// Use verbose output (-v, -vv, -vvv, etc.)
     #[clap(short, long)]
     #[clap(parse(from_occurrences))]
     verbose: u8,
 }
+
+/// Formats the final state can be printed in.
+#[derive(ArgEnum, Clone, Copy, Debug)]
+enum DumpFormat {
+    Text,
+    Json,
+}
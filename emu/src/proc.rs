@@ -1,15 +1,40 @@
 use std::fmt::{self, Display};
 
+use isa::{Encoding, Instruction};
+
 use super::{uarch, BANKSIZE, RAMSIZE, WORDSIZE};
-use crate::inst::{self, Instruction};
-use crate::ram::Ram;
+use crate::bus::{Bus, Map, Part};
+use crate::ctl;
+use crate::hist::{History, Record};
+use crate::inst::{self, Execute};
 use crate::reg::{Bank, Register};
+use crate::stop::{Fault, FaultKind, StopReason};
+use crate::sys::SysHandler;
+use crate::timing::Timing;
+use crate::watch::{Watch, Watchpoint};
 
 #[derive(Debug, Default)]
 pub struct Processor {
     pub regs: Bank<BANKSIZE>,
     pub sr: Register,
-    pub ram: Ram<RAMSIZE>,
+    pub bus: Map,
+    pub enc: Encoding,
+    pub halted: bool,
+    pub cycles: u64,
+    /// Number of instructions executed.
+    pub instrs: u64,
+    pub timing: Timing,
+    pub sys: Box<dyn SysHandler>,
+    pub hist: History,
+    /// Address of the most recently executed instruction.
+    pub last: uarch,
+    /// Writes made by the instruction being executed, as `(addr, old, new)`.
+    pub writes: Vec<(uarch, uarch, uarch)>,
+    pub watches: Vec<Watchpoint>,
+    /// First watchpoint hit by the instruction being executed.
+    pub watched: Option<StopReason>,
+    /// Fault raised by the instruction being executed.
+    fault: Option<FaultKind>,
 }
 
 impl Processor {
@@ -19,22 +44,221 @@ impl Processor {
         }
     }
 
-    pub fn cycle(&mut self) -> Box<dyn Instruction> {
+    /// Executes the next instruction, returning it.
+    ///
+    /// Returns `None` if the instruction faulted and control was passed to the
+    /// fault handler.
+    pub fn cycle(&mut self) -> Result<Option<Instruction>, Fault> {
+        self.checkpoint();
+        self.writes.clear();
+        self.interrupt();
         let pc = *self.regs[15];
-        *self.regs[15] += WORDSIZE as uarch;
-        let word = self.ram[pc];
-        let instr = inst::decode(word);
+        self.last = pc;
+        if let Some(rec) = self.hist.last_mut() {
+            rec.pc = pc;
+        }
+        match self.exec(pc) {
+            Ok(instr) => Ok(Some(instr)),
+            Err(kind) => {
+                // Rewind to the faulting instruction
+                *self.regs[15] = pc;
+                self.trap(Fault { pc, kind })
+                    .map(|_| None)
+                    .inspect_err(|_| {
+                        // Forget the instruction if nothing happened
+                        if self.hist.last_mut().is_some_and(|rec| rec.regs[15] == pc) {
+                            self.hist.pop();
+                        }
+                    })
+            }
+        }
+    }
+
+    /// Records the current state in the history, if enabled.
+    fn checkpoint(&mut self) {
+        if self.hist.limit() == 0 {
+            return;
+        }
+        let mut regs = [0; BANKSIZE];
+        for (value, reg) in regs.iter_mut().zip(self.regs.iter()) {
+            *value = **reg;
+        }
+        self.hist.push(Record {
+            regs,
+            sr: *self.sr,
+            halted: self.halted,
+            cycles: self.cycles,
+            instrs: self.instrs,
+            ctl: None,
+            devices: Vec::new(),
+            pc: *self.regs[15],
+            writes: Vec::new(),
+            returned: None,
+        });
+    }
+
+    /// Undoes the most recently recorded instruction, returning its record.
+    ///
+    /// Returns `None` if the history is empty.
+    pub fn undo(&mut self) -> Option<Record> {
+        let rec = self.hist.pop()?;
+        for &(addr, old, _) in rec.writes.iter().rev() {
+            self.bus.ram[addr] = old;
+        }
+        if let Some(ctl) = &rec.ctl {
+            self.bus.ctl.restore(ctl);
+        }
+        for (line, _, state) in &rec.devices {
+            self.bus.restore_device(*line, state);
+        }
+        // Devices which were not saved only ticked in ways that can be reversed
+        let ticks = self.cycles - rec.cycles;
+        self.bus.untick(|line| {
+            rec.devices
+                .iter()
+                .find(|&&(saved, ..)| saved == line)
+                .map_or(ticks, |&(_, ticks, _)| ticks)
+        });
+        for (reg, value) in self.regs.iter_mut().zip(rec.regs) {
+            **reg = value;
+        }
+        *self.sr = rec.sr;
+        self.halted = rec.halted;
+        self.cycles = rec.cycles;
+        self.instrs = rec.instrs;
+        Some(rec)
+    }
+
+    /// Saves a part of the map in the history, before the instruction being
+    /// executed first changes it.
+    fn save(&mut self, part: Part) {
+        if let Some(rec) = self.hist.last_mut() {
+            rec.save(&self.bus, part, self.cycles - rec.cycles);
+        }
+    }
+
+    /// Saves the part of the map an address belongs to, before accessing it.
+    fn touch(&mut self, addr: uarch) {
+        if let Some(part) = self.bus.part(addr) {
+            self.save(part);
+        }
+    }
+
+    fn exec(&mut self, pc: uarch) -> Result<Instruction, FaultKind> {
+        self.touch(pc);
+        let word = self.bus.read(pc).ok_or(FaultKind::Fetch)?;
+        *self.regs[15] = pc.wrapping_add(WORDSIZE as uarch);
+        let instr = inst::decode(word, self.enc).map_err(|_| FaultKind::Undefined(word))?;
+        let cost = self.timing.cost(&instr, *self.sr);
+        self.cycles += cost;
+        self.instrs += 1;
+        for tick in 0..cost {
+            let hist = &mut self.hist;
+            self.bus.tick(|bus, part| {
+                if let Some(rec) = hist.last_mut() {
+                    rec.save(bus, part, tick);
+                }
+            });
+        }
         instr.execute(self);
-        instr
+        match self.fault.take() {
+            Some(kind) => Err(kind),
+            None => Ok(instr),
+        }
+    }
+
+    /// Passes a fault to the fault handler, if one is installed.
+    fn trap(&mut self, fault: Fault) -> Result<(), Fault> {
+        let vbar = self.bus.ctl.vbar;
+        if vbar == 0 {
+            return Err(fault);
+        }
+        self.touch(vbar.wrapping_add(ctl::FAULT));
+        let handler = self.bus.read(vbar.wrapping_add(ctl::FAULT)).ok_or(fault)?;
+        self.save(Part::Ctl);
+        self.bus.ctl.raise(fault);
+        *self.regs[15] = handler;
+        Ok(())
+    }
+
+    /// Enters the interrupt handler if an enabled interrupt is pending.
+    ///
+    /// The PC and status register are saved to be restored by `rti`, and
+    /// interrupts are disabled until then.
+    fn interrupt(&mut self) {
+        let vbar = self.bus.ctl.vbar;
+        if *self.sr & Flag::Interrupt.bit() == 0 || !self.bus.ctl.pending() || vbar == 0 {
+            return;
+        }
+        self.touch(vbar.wrapping_add(ctl::IRQ));
+        let Some(handler) = self.bus.read(vbar.wrapping_add(ctl::IRQ)) else {
+            return;
+        };
+        self.save(Part::Ctl);
+        self.bus.ctl.ipc = *self.regs[15];
+        self.bus.ctl.isr = *self.sr;
+        *self.sr &= !Flag::Interrupt.bit();
+        *self.regs[15] = handler;
+    }
+
+    /// Returns from the interrupt handler.
+    pub fn rti(&mut self) {
+        *self.regs[15] = self.bus.ctl.ipc;
+        *self.sr = self.bus.ctl.isr;
+    }
+
+    /// Reads a word from the bus, raising a fault if it fails.
+    pub fn read(&mut self, addr: uarch) -> Option<uarch> {
+        // Reads may have side effects on devices
+        self.touch(addr);
+        let word = self.bus.read(addr);
+        match word {
+            Some(word) => self.watch(addr, word, word, false),
+            None => self.fault = Some(FaultKind::Bus(addr)),
+        }
+        word
+    }
+
+    /// Writes a word to the bus, raising a fault if it fails.
+    pub fn write(&mut self, addr: uarch, word: uarch) -> Option<()> {
+        self.touch(addr);
+        let old = self.bus.peek(addr);
+        let res = self.bus.write(addr, word);
+        match res {
+            Some(()) => self.watch(addr, old.unwrap_or(word), word, true),
+            None => self.fault = Some(FaultKind::Bus(addr)),
+        }
+        if let (Some(()), Some(old)) = (res, old) {
+            self.writes.push((addr, old, word));
+            // Log writes to RAM, so they can be undone
+            if let Some(rec) = self.hist.last_mut().filter(|_| (addr as usize) < RAMSIZE) {
+                rec.writes.push((addr, old, word));
+            }
+        }
+        res
+    }
+
+    /// Checks an access against the watchpoints.
+    fn watch(&mut self, addr: uarch, old: uarch, new: uarch, write: bool) {
+        if self.watched.is_none() && self.watches.iter().any(|wp| wp.matches(addr, write)) {
+            self.watched = Some(StopReason::Watchpoint {
+                addr,
+                old,
+                new,
+                pc: self.last,
+                access: match write {
+                    true => Watch::Write,
+                    false => Watch::Read,
+                },
+            });
+        }
     }
 
     fn flags(&self) -> Vec<Flag> {
-        let mut flags = Vec::new();
-        (*self.sr & 0x0008 != 0).then(|| flags.push(Flag::Carry));
-        (*self.sr & 0x0004 != 0).then(|| flags.push(Flag::Overflow));
-        (*self.sr & 0x0002 != 0).then(|| flags.push(Flag::Negative));
-        (*self.sr & 0x0001 != 0).then(|| flags.push(Flag::Zero));
-        flags
+        Flag::ALL
+            .into_iter()
+            .filter(|flag| *self.sr & flag.bit() != 0)
+            .collect()
     }
 }
 
@@ -49,14 +273,56 @@ impl Display for Processor {
             }
         }
         write!(f, "SR : {}, {:?}", self.sr, self.flags())?;
+        if self.halted {
+            write!(f, " (halted)")?;
+        }
         write!(f, "")
     }
 }
 
-#[derive(Debug)]
-enum Flag {
+/// Status register flags.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Flag {
+    Interrupt,
     Carry,
     Overflow,
     Negative,
     Zero,
 }
+
+impl Flag {
+    pub const ALL: [Self; 5] = [
+        Self::Interrupt,
+        Self::Carry,
+        Self::Overflow,
+        Self::Negative,
+        Self::Zero,
+    ];
+
+    /// Bit occupied by this flag within the status register.
+    pub fn bit(self) -> uarch {
+        match self {
+            Self::Interrupt => 0x0010,
+            Self::Carry => 0x0008,
+            Self::Overflow => 0x0004,
+            Self::Negative => 0x0002,
+            Self::Zero => 0x0001,
+        }
+    }
+
+    /// Short name of this flag, as used by the debugger.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Interrupt => "i",
+            Self::Carry => "c",
+            Self::Overflow => "v",
+            Self::Negative => "n",
+            Self::Zero => "z",
+        }
+    }
+
+    /// Looks up a flag by its short name.
+    pub fn lookup(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|flag| flag.name() == name)
+    }
+}
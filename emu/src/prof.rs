@@ -0,0 +1,416 @@
+//! Execution profiler.
+//!
+//! A [`Profiler`] counts how often each instruction is executed and how many
+//! cycles it takes. Cycles are also attributed to functions, which are found
+//! by following calls and returns:
+//!
+//! - Taken branches which link, such as `call`, enter the function at their
+//!   target.
+//! - Interrupts and faults enter their handler as if it were called.
+//! - `mov pc, lr`, `pop pc` and `rti` return to the caller.
+//!
+//! Functions are identified by their entry address. A function's exclusive
+//! cycles are those spent executing its own instructions, while its inclusive
+//! cycles also count those spent in its callees. Recursive calls are only
+//! counted once towards inclusive cycles.
+//!
+//! Profiles can be written as a text report, or as folded stacks for use with
+//! flame graph tools:
+//!
+//! ```text
+//! _start;_main;_is_prime;_divide 1520
+//! ```
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use isa::inst::{ldr, mov, Ldr, Mov};
+use isa::{Instruction, Op2};
+
+use crate::proc::Processor;
+use crate::sym::Symbols;
+use crate::uarch;
+
+/// Samples taken at an instruction's address.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Line {
+    /// Number of times executed.
+    pub count: u64,
+    pub cycles: u64,
+    /// Most recent instruction executed at the address.
+    pub instr: Option<Instruction>,
+}
+
+/// Samples taken within a function.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Func {
+    pub calls: u64,
+    /// Cycles spent in the function itself.
+    pub exclusive: u64,
+    /// Cycles spent in the function and its callees.
+    pub inclusive: u64,
+}
+
+/// Samples taken for calls from one function to another.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Edge {
+    pub calls: u64,
+    /// Cycles spent in the callee and its callees.
+    pub cycles: u64,
+}
+
+/// A function being executed.
+#[derive(Clone, Debug)]
+pub(crate) struct Frame {
+    func: uarch,
+    /// Number of cycles executed when the function was entered.
+    entry: u64,
+    /// Exclusive cycles not yet added to the folded stacks.
+    pending: u64,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Profiler {
+    lines: BTreeMap<uarch, Line>,
+    funcs: BTreeMap<uarch, Func>,
+    edges: BTreeMap<(uarch, uarch), Edge>,
+    stacks: BTreeMap<Vec<uarch>, u64>,
+    frames: Vec<Frame>,
+    /// Number of cycles executed as of the last sample.
+    now: u64,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Samples an instruction executed by the processor.
+    ///
+    /// Takes the PC, status register and cycle count from before the
+    /// instruction, along with the instruction executed, if it did not fault.
+    /// Returns the frame of the function returned from, if any.
+    pub(crate) fn sample(
+        &mut self,
+        from: uarch,
+        sr: uarch,
+        start: u64,
+        instr: Option<Instruction>,
+        proc: &Processor,
+    ) -> Option<Frame> {
+        // Start in the function being executed
+        if self.frames.is_empty() {
+            self.funcs.entry(from).or_default().calls += 1;
+            self.frames.push(Frame {
+                func: from,
+                entry: start,
+                pending: 0,
+            });
+        }
+        // Interrupts enter their handler before the instruction
+        let pc = proc.last;
+        if pc != from {
+            self.call(pc, start);
+        }
+        // Attribute the instruction's cycles
+        let cost = proc.cycles - start;
+        let line = self.lines.entry(pc).or_default();
+        line.count += 1;
+        line.cycles += cost;
+        line.instr = instr.or(line.instr);
+        let frame = self.frames.last_mut().unwrap();
+        frame.pending += cost;
+        self.funcs.entry(frame.func).or_default().exclusive += cost;
+        self.now = proc.cycles;
+        // Follow calls and returns, never returning from the outermost function
+        let next = *proc.regs[15];
+        match instr {
+            Some(Instruction::Bra(bra)) if bra.link && bra.cond.eval(sr) => {
+                self.call(next, proc.cycles);
+                None
+            }
+            Some(Instruction::Mov(Mov {
+                op1: 15,
+                op2: Op2::Reg(14),
+                mode: mov::Mode::Mov,
+            }))
+            | Some(Instruction::Ldr(Ldr {
+                op1: 15,
+                mode: ldr::Mode::Pop,
+                ..
+            }))
+            | Some(Instruction::Rti(_))
+                if self.frames.len() > 1 =>
+            {
+                Some(self.ret(proc.cycles))
+            }
+            // Faults enter their handler
+            None => {
+                self.call(next, proc.cycles);
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Enters a function.
+    fn call(&mut self, func: uarch, now: u64) {
+        self.fold();
+        let caller = self.frames.last().unwrap().func;
+        self.funcs.entry(func).or_default().calls += 1;
+        self.edges.entry((caller, func)).or_default().calls += 1;
+        self.frames.push(Frame {
+            func,
+            entry: now,
+            pending: 0,
+        });
+    }
+
+    /// Returns from the current function, returning its frame.
+    fn ret(&mut self, now: u64) -> Frame {
+        self.fold();
+        let frame = self.frames.pop().unwrap();
+        let cycles = now - frame.entry;
+        // Only count the outermost of any recursive calls
+        if self.frames.iter().all(|other| other.func != frame.func) {
+            self.funcs.entry(frame.func).or_default().inclusive += cycles;
+        }
+        if let Some(caller) = self.frames.last() {
+            let edge = (caller.func, frame.func);
+            let nested = self
+                .frames
+                .windows(2)
+                .any(|pair| (pair[0].func, pair[1].func) == edge);
+            if !nested {
+                self.edges.entry(edge).or_default().cycles += cycles;
+            }
+        }
+        frame
+    }
+
+    /// Adds the current function's pending cycles to the folded stacks.
+    fn fold(&mut self) {
+        let Some(frame) = self.frames.last_mut() else {
+            return;
+        };
+        if frame.pending == 0 {
+            return;
+        }
+        let cycles = std::mem::take(&mut frame.pending);
+        let stack = self.frames.iter().map(|frame| frame.func).collect();
+        *self.stacks.entry(stack).or_default() += cycles;
+    }
+
+    /// Rewinds to an earlier cycle count, such as after reverse execution,
+    /// re-entering the function returned from by an undone instruction.
+    ///
+    /// Functions entered after it are forgotten without returning, although
+    /// the samples taken within them are kept.
+    pub(crate) fn rewind(&mut self, now: u64, returned: Option<Frame>) {
+        self.fold();
+        let open = self.frames.partition_point(|frame| frame.entry <= now);
+        self.frames.truncate(open);
+        self.frames.extend(returned);
+        self.now = self.now.min(now);
+    }
+
+    /// Returns a copy of the profile with every open function returned from.
+    fn settled(&self) -> Self {
+        let mut prof = self.clone();
+        while !prof.frames.is_empty() {
+            prof.ret(prof.now);
+        }
+        prof
+    }
+
+    /// Samples taken at each instruction's address.
+    pub fn lines(&self) -> &BTreeMap<uarch, Line> {
+        &self.lines
+    }
+
+    /// Samples taken within each function, by entry address.
+    ///
+    /// Functions which have not yet returned are counted up to now.
+    pub fn funcs(&self) -> BTreeMap<uarch, Func> {
+        self.settled().funcs
+    }
+
+    /// Samples taken for each call from one function to another, by the
+    /// entry addresses of the caller and callee.
+    pub fn edges(&self) -> BTreeMap<(uarch, uarch), Edge> {
+        self.settled().edges
+    }
+
+    /// Exclusive cycles spent in each stack of functions, outermost first.
+    pub fn stacks(&self) -> BTreeMap<Vec<uarch>, u64> {
+        self.settled().stacks
+    }
+
+    /// Writes a text report of the flat profile, call graph, and instructions.
+    pub fn report(&self, syms: &Symbols, out: &mut impl Write) -> io::Result<()> {
+        let prof = self.settled();
+        let total = prof.now.max(1);
+        let name = |addr| syms.name(addr).unwrap_or_else(|| format!("{:#06x}", addr));
+        // Write the flat profile, most expensive first
+        let mut funcs = prof.funcs.iter().collect::<Vec<_>>();
+        funcs.sort_by_key(|&(&addr, func)| (u64::MAX - func.exclusive, addr));
+        writeln!(out, "Flat profile:")?;
+        writeln!(out)?;
+        writeln!(
+            out,
+            "{:>7} {:>12} {:>12} {:>8}  function",
+            "self%", "self", "total", "calls"
+        )?;
+        for (&addr, func) in funcs {
+            writeln!(
+                out,
+                "{:>6.2}% {:>12} {:>12} {:>8}  {}",
+                100.0 * func.exclusive as f64 / total as f64,
+                func.exclusive,
+                func.inclusive,
+                func.calls,
+                name(addr),
+            )?;
+        }
+        // Write the call graph, grouped by caller
+        writeln!(out)?;
+        writeln!(out, "Call graph:")?;
+        let mut caller = None;
+        for (&(from, to), edge) in &prof.edges {
+            if caller != Some(from) {
+                writeln!(out)?;
+                writeln!(out, "{}", name(from))?;
+                caller = Some(from);
+            }
+            writeln!(
+                out,
+                "  {:>8} calls {:>12} cycles  -> {}",
+                edge.calls,
+                edge.cycles,
+                name(to)
+            )?;
+        }
+        // Write the samples of each instruction
+        writeln!(out)?;
+        writeln!(out, "Instructions:")?;
+        writeln!(out)?;
+        writeln!(out, "{:>10} {:>12}  address", "count", "cycles")?;
+        for (&addr, line) in &prof.lines {
+            write!(
+                out,
+                "{:>10} {:>12}  {}",
+                line.count,
+                line.cycles,
+                syms.symbolize(addr)
+            )?;
+            match line.instr {
+                Some(instr) => writeln!(out, ": {}", instr)?,
+                None => writeln!(out)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the folded stacks, one per line.
+    pub fn write_folded(&self, syms: &Symbols, out: &mut impl Write) -> io::Result<()> {
+        let name = |addr| syms.name(addr).unwrap_or_else(|| format!("{:#06x}", addr));
+        for (stack, cycles) in self.stacks() {
+            let stack = stack.into_iter().map(name).collect::<Vec<_>>();
+            writeln!(out, "{} {}", stack.join(";"), cycles)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{uarch, Emulator, WORDSIZE};
+
+    /// Loads a program which calls a function twice, which itself calls
+    /// another.
+    fn load(e: &mut Emulator) {
+        // _start: call _f; call _f; hlt
+        // _f:     push lr; call _g; pop pc
+        // _g:     mul r0, 0x1; mov pc, lr
+        for (addr, word) in [
+            0x0002, 0x0482, 0x0481, 0x0c00, 0x2e40, 0x0481, 0x3f40, 0x6081, 0x7f0e,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            e.write((addr * WORDSIZE) as uarch, word).unwrap();
+        }
+        e.reset();
+        e.set_reg(13, 0x1000);
+    }
+
+    #[test]
+    fn profile() {
+        let mut e = Emulator::new();
+        e.set_profiling(true);
+        load(&mut e);
+        e.run();
+        let prof = e.profiler().unwrap();
+        let funcs = prof.funcs();
+        // _start executes 3 instructions: 2 taken calls, then hlt
+        assert_eq!(funcs[&0x0002].calls, 1);
+        assert_eq!(funcs[&0x0002].exclusive, 2 + 2 + 1);
+        assert_eq!(funcs[&0x0002].inclusive, e.cycles());
+        // _f executes push, a taken call, and a returning pop
+        assert_eq!(funcs[&0x0008].calls, 2);
+        assert_eq!(funcs[&0x0008].exclusive, 2 * (2 + 2 + 3));
+        // _g executes mul and a returning mov
+        assert_eq!(funcs[&0x000e].calls, 2);
+        assert_eq!(funcs[&0x000e].exclusive, 2 * (3 + 2));
+        assert_eq!(funcs[&0x0008].inclusive, 2 * (7 + 5));
+        let edges = prof.edges();
+        assert_eq!(edges[&(0x0002, 0x0008)].calls, 2);
+        assert_eq!(edges[&(0x0008, 0x000e)].cycles, 10);
+        assert_eq!(prof.lines()[&0x000e].count, 2);
+        assert_eq!(prof.lines()[&0x000e].cycles, 6);
+        assert_eq!(prof.stacks()[&vec![0x0002, 0x0008, 0x000e]], 10);
+    }
+
+    #[test]
+    fn rewind() {
+        let mut e = Emulator::new();
+        e.set_history(100);
+        e.set_profiling(true);
+        load(&mut e);
+        for _ in 0..3 {
+            e.step().unwrap();
+        }
+        // Ensure functions entered after undoing are forgotten
+        for _ in 0..3 {
+            assert!(e.step_back());
+        }
+        e.step().unwrap();
+        let funcs = e.profiler().unwrap().funcs();
+        assert_eq!(funcs[&0x0002].inclusive, e.cycles());
+        assert_eq!(funcs[&0x0008].inclusive, 0);
+        assert_eq!(funcs[&0x000e].inclusive, 0);
+        // Ensure profiling resumes normally
+        e.run();
+        let funcs = e.profiler().unwrap().funcs();
+        assert_eq!(funcs[&0x0008].calls, 3);
+        assert_eq!(funcs[&0x0002].inclusive, e.cycles());
+    }
+
+    #[test]
+    fn unreturn() {
+        let mut e = Emulator::new();
+        e.set_history(100);
+        e.set_profiling(true);
+        load(&mut e);
+        // Step through _g's return, then undo it
+        for _ in 0..5 {
+            e.step().unwrap();
+        }
+        assert!(e.step_back());
+        // Ensure the return is repeated from _g, not _f
+        e.run();
+        let funcs = e.profiler().unwrap().funcs();
+        assert_eq!(funcs[&0x0002].exclusive, 2 + 2 + 1);
+        assert_eq!(funcs[&0x0008].inclusive, 2 * (7 + 5));
+    }
+}
@@ -51,7 +51,7 @@ impl<const N: usize> Index<uarch> for Ram<N> {
 
     fn index(&self, idx: uarch) -> &Self::Output {
         let idx = idx as usize;
-        assert!((idx % WORDSIZE) == 0);
+        assert!(idx.is_multiple_of(WORDSIZE));
         unsafe { &self.0.align_to::<uarch>().1[idx / WORDSIZE] }
     }
 }
@@ -59,7 +59,7 @@ impl<const N: usize> Index<uarch> for Ram<N> {
 impl<const N: usize> IndexMut<uarch> for Ram<N> {
     fn index_mut(&mut self, idx: uarch) -> &mut Self::Output {
         let idx = idx as usize;
-        assert!((idx % WORDSIZE) == 0);
+        assert!(idx.is_multiple_of(WORDSIZE));
         unsafe { &mut self.0.align_to_mut::<uarch>().1[idx / WORDSIZE] }
     }
 }
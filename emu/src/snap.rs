@@ -0,0 +1,266 @@
+//! Machine state snapshots.
+//!
+//! A [`Snapshot`] captures everything needed to resume execution later: the
+//! registers, memory, system control block and the state of each device.
+//!
+//! # Format
+//!
+//! All integers are stored little-endian. Blobs are stored as a `u16` length
+//! followed by that many bytes.
+//!
+//! | Field     | Contents                                              |
+//! | --------- | ----------------------------------------------------- |
+//! | Header    | magic `KSNP`, `u16` version, `u8` encoding            |
+//! | Processor | `u16` registers, `u16` SR, `u8` halted                |
+//! | Counters  | `u64` cycles, `u64` instructions                      |
+//! | Memory    | `u16` write-protected length, RAM blob                |
+//! | Control   | system control block blob                             |
+//! | Devices   | `u16` count; each a blob                              |
+
+use std::error::Error;
+use std::fmt::{self, Display};
+
+use isa::Encoding;
+
+use crate::bus::Bus;
+use crate::proc::Processor;
+use crate::{uarch, BANKSIZE};
+
+const MAGIC: &[u8; 4] = b"KSNP";
+const VERSION: u16 = 2;
+
+/// Saved state of the machine.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Snapshot {
+    enc: Encoding,
+    regs: [uarch; BANKSIZE],
+    sr: uarch,
+    halted: bool,
+    cycles: u64,
+    instrs: u64,
+    rom: usize,
+    ram: Vec<u8>,
+    ctl: Vec<u8>,
+    devices: Vec<Vec<u8>>,
+}
+
+impl Snapshot {
+    /// Captures the state of a processor.
+    pub(crate) fn capture(proc: &Processor) -> Self {
+        let mut regs = [0; BANKSIZE];
+        for (value, reg) in regs.iter_mut().zip(proc.regs.iter()) {
+            *value = **reg;
+        }
+        Self {
+            enc: proc.enc,
+            regs,
+            sr: *proc.sr,
+            halted: proc.halted,
+            cycles: proc.cycles,
+            instrs: proc.instrs,
+            rom: proc.bus.protected(),
+            ram: proc.bus.ram.0.to_vec(),
+            ctl: proc.bus.ctl.save(),
+            devices: proc.bus.save_devices(),
+        }
+    }
+
+    /// Restores the state of a processor.
+    ///
+    /// The processor must have the same devices attached as when captured.
+    pub(crate) fn apply(&self, proc: &mut Processor) -> Result<(), SnapshotError> {
+        // Restore devices first, as they may reject their state
+        proc.bus
+            .restore_devices(&self.devices)
+            .ok_or(SnapshotError::Devices)?;
+        proc.bus
+            .ctl
+            .restore(&self.ctl)
+            .ok_or(SnapshotError::Malformed)?;
+        proc.enc = self.enc;
+        for (reg, &value) in proc.regs.iter_mut().zip(self.regs.iter()) {
+            **reg = value;
+        }
+        *proc.sr = self.sr;
+        proc.halted = self.halted;
+        proc.cycles = self.cycles;
+        proc.instrs = self.instrs;
+        proc.bus.protect(self.rom);
+        proc.bus.ram.0.copy_from_slice(&self.ram);
+        Ok(())
+    }
+
+    /// Number of cycles executed when the snapshot was taken.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Writer::default();
+        // Write header
+        buf.0.extend(MAGIC);
+        buf.u16(VERSION);
+        buf.u8(match self.enc {
+            Encoding::V0 => 0,
+            Encoding::V1 => 1,
+        });
+        // Write processor
+        self.regs.iter().for_each(|&reg| buf.u16(reg));
+        buf.u16(self.sr);
+        buf.u8(self.halted as u8);
+        // Write counters
+        buf.u64(self.cycles);
+        buf.u64(self.instrs);
+        // Write memory
+        buf.u16(self.rom as u16);
+        buf.blob(&self.ram);
+        // Write control block
+        buf.blob(&self.ctl);
+        // Write devices
+        buf.u16(self.devices.len() as u16);
+        self.devices.iter().for_each(|dev| buf.blob(dev));
+        buf.0
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        let mut buf = Reader(bytes);
+        // Read header
+        (buf.take(MAGIC.len())? == MAGIC)
+            .then_some(())
+            .ok_or(SnapshotError::BadMagic)?;
+        let version = buf.u16()?;
+        (version == VERSION)
+            .then_some(())
+            .ok_or(SnapshotError::Unsupported(version))?;
+        let enc = match buf.u8()? {
+            0 => Encoding::V0,
+            1 => Encoding::V1,
+            _ => return Err(SnapshotError::Malformed),
+        };
+        // Read processor
+        let mut regs = [0; BANKSIZE];
+        for reg in regs.iter_mut() {
+            *reg = buf.u16()?;
+        }
+        let sr = buf.u16()?;
+        let halted = match buf.u8()? {
+            0 => false,
+            1 => true,
+            _ => return Err(SnapshotError::Malformed),
+        };
+        // Read counters
+        let cycles = buf.u64()?;
+        let instrs = buf.u64()?;
+        // Read memory
+        let rom = buf.u16()? as usize;
+        let ram = buf.blob()?;
+        (ram.len() == crate::RAMSIZE)
+            .then_some(())
+            .ok_or(SnapshotError::Malformed)?;
+        // Read control block
+        let ctl = buf.blob()?;
+        // Read devices
+        let devices = (0..buf.u16()?)
+            .map(|_| buf.blob())
+            .collect::<Result<_, _>>()?;
+        // Ensure nothing is left over
+        buf.0
+            .is_empty()
+            .then_some(())
+            .ok_or(SnapshotError::Malformed)?;
+        Ok(Self {
+            enc,
+            regs,
+            sr,
+            halted,
+            cycles,
+            instrs,
+            rom,
+            ram,
+            ctl,
+            devices,
+        })
+    }
+}
+
+#[derive(Default)]
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+
+    fn u16(&mut self, value: u16) {
+        self.0.extend(value.to_le_bytes());
+    }
+
+    fn u64(&mut self, value: u64) {
+        self.0.extend(value.to_le_bytes());
+    }
+
+    fn blob(&mut self, value: &[u8]) {
+        self.u16(value.len() as u16);
+        self.0.extend(value);
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], SnapshotError> {
+        (len <= self.0.len())
+            .then_some(())
+            .ok_or(SnapshotError::Truncated)?;
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, SnapshotError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, SnapshotError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u64(&mut self) -> Result<u64, SnapshotError> {
+        let bytes = self.take(8)?;
+        Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn blob(&mut self) -> Result<Vec<u8>, SnapshotError> {
+        let len = self.u16()? as usize;
+        Ok(self.take(len)?.to_vec())
+    }
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    BadMagic,
+    Unsupported(u16),
+    Truncated,
+    Malformed,
+    /// The snapshot's devices do not match those attached.
+    Devices,
+}
+
+impl Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::BadMagic => "Not a snapshot file".to_string(),
+                Self::Unsupported(version) => format!("Unsupported snapshot version {}", version),
+                Self::Truncated => "Snapshot file is truncated".to_string(),
+                Self::Malformed => "Snapshot file is malformed".to_string(),
+                Self::Devices => "Snapshot devices do not match those attached".to_string(),
+            }
+        )
+    }
+}
+
+impl Error for SnapshotError {}
@@ -0,0 +1,88 @@
+use std::error::Error;
+use std::fmt::{self, Display};
+
+use crate::uarch;
+use crate::watch::Watch;
+
+/// Reason the emulator stopped running.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StopReason {
+    /// The processor halted with an exit status.
+    Halt(uarch),
+    /// Execution reached a breakpoint at an address.
+    Breakpoint(uarch),
+    /// The processor faulted.
+    Fault(Fault),
+    /// An instruction accessed a watched address.
+    ///
+    /// For reads, the old and new words are both the word read.
+    Watchpoint {
+        addr: uarch,
+        old: uarch,
+        new: uarch,
+        /// Address of the instruction which made the access.
+        pc: uarch,
+        /// Kind of access made, either [`Watch::Read`] or [`Watch::Write`].
+        access: Watch,
+    },
+    /// The PC stopped advancing at an address, as in a branch to itself.
+    Idle(uarch),
+    /// The cycle budget was used up.
+    CycleLimit,
+    /// The caller's stop condition was met.
+    Condition,
+    /// Reverse execution reached the oldest recorded instruction.
+    HistoryExhausted,
+}
+
+impl Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Halt(status) => write!(f, "Halted with status {}", status),
+            Self::Breakpoint(addr) => write!(f, "Breakpoint at {:#06x}", addr),
+            Self::Fault(fault) => write!(f, "{}", fault),
+            Self::Watchpoint {
+                addr, old, new, pc, ..
+            } => write!(
+                f,
+                "Watchpoint at {:#06x} hit by {:#06x}: {:#06x} -> {:#06x}",
+                addr, pc, old, new
+            ),
+            Self::Idle(addr) => write!(f, "Idle loop at {:#06x}", addr),
+            Self::CycleLimit => write!(f, "Cycle limit reached"),
+            Self::Condition => write!(f, "Stop condition met"),
+            Self::HistoryExhausted => write!(f, "Reached the start of the recorded history"),
+        }
+    }
+}
+
+/// An architectural fault raised by a bad program.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Fault {
+    /// Address of the faulting instruction.
+    pub pc: uarch,
+    pub kind: FaultKind,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FaultKind {
+    /// The PC did not point to a readable word.
+    Fetch,
+    /// The instruction word could not be decoded.
+    Undefined(uarch),
+    /// A load or store to an address failed.
+    Bus(uarch),
+}
+
+impl Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            FaultKind::Fetch => write!(f, "Instruction fetch fault"),
+            FaultKind::Undefined(word) => write!(f, "Undefined instruction {:#06x}", word),
+            FaultKind::Bus(addr) => write!(f, "Bus error accessing {:#06x}", addr),
+        }?;
+        write!(f, " at {:#06x}", self.pc)
+    }
+}
+
+impl Error for Fault {}
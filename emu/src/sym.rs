@@ -0,0 +1,176 @@
+//! Symbol maps.
+//!
+//! The assembler can write a map of the symbols in a program, where each line
+//! holds an address followed by a symbol name:
+//!
+//! ```text
+//! 0x0002 _main
+//! 0x000c _divide
+//! ```
+
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use isa::reg;
+
+use crate::uarch;
+
+/// Symbols of a program, ordered by address.
+#[derive(Clone, Debug, Default)]
+pub struct Symbols(Vec<(String, uarch)>);
+
+impl Symbols {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads symbols from a map, as written by the assembler.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Ok(fs::read_to_string(path)?.parse()?)
+    }
+
+    /// Adds a symbol.
+    pub fn insert(&mut self, name: &str, addr: uarch) {
+        let idx = self.0.partition_point(|&(_, other)| other <= addr);
+        self.0.insert(idx, (name.to_string(), addr));
+    }
+
+    /// Finds the address of a symbol by its name.
+    pub fn lookup(&self, name: &str) -> Option<uarch> {
+        self.0
+            .iter()
+            .find(|(other, _)| other == name)
+            .map(|&(_, addr)| addr)
+    }
+
+    /// Resolves a location, which is either an address or a symbol's name.
+    pub fn resolve(&self, loc: &str) -> Option<uarch> {
+        parse_num(loc).or_else(|| self.lookup(loc))
+    }
+
+    /// Finds the nearest symbol at or preceding an address.
+    pub fn nearest(&self, addr: uarch) -> Option<(&str, uarch)> {
+        self.0
+            .iter()
+            .rev()
+            .find(|&&(_, base)| base <= addr)
+            .map(|(name, base)| (name.as_str(), *base))
+    }
+
+    /// Names an address relative to the nearest preceding symbol, such as
+    /// `_main+4`.
+    ///
+    /// Returns `None` if no symbol precedes the address.
+    pub fn name(&self, addr: uarch) -> Option<String> {
+        self.nearest(addr).map(|(name, base)| match addr - base {
+            0 => name.to_string(),
+            off => format!("{}+{}", name, off),
+        })
+    }
+
+    /// Formats an address along with its name, such as `0x0004 <_main+4>`.
+    pub fn symbolize(&self, addr: uarch) -> String {
+        match self.name(addr) {
+            Some(name) => format!("{:#06x} <{}>", addr, name),
+            None => format!("{:#06x}", addr),
+        }
+    }
+}
+
+impl FromStr for Symbols {
+    type Err = SymbolError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut symbols = Vec::new();
+        for line in s.lines() {
+            let symbol = match line.split_whitespace().collect::<Vec<_>>()[..] {
+                [] => continue,
+                [addr, name] => parse_num(addr).map(|addr| (name.to_string(), addr)),
+                _ => None,
+            }
+            .ok_or_else(|| SymbolError::BadLine(line.to_string()))?;
+            symbols.push(symbol);
+        }
+        symbols.sort_by_key(|&(_, addr)| addr);
+        Ok(Self(symbols))
+    }
+}
+
+/// Parses a number, using the same prefixes as the assembler.
+pub(crate) fn parse_num(s: &str) -> Option<uarch> {
+    let (neg, s) = match s.strip_prefix('-') {
+        Some(s) => (true, s),
+        None => (false, s),
+    };
+    let (radix, digits) = match s.get(..2) {
+        Some("0b") => (2, &s[2..]),
+        Some("0o") => (8, &s[2..]),
+        Some("0d") => (10, &s[2..]),
+        Some("0x") => (16, &s[2..]),
+        _ => (10, s),
+    };
+    let value = uarch::from_str_radix(digits, radix).ok()?;
+    Some(match neg {
+        true => value.wrapping_neg(),
+        false => value,
+    })
+}
+
+/// Parses a register by its number or conventional name.
+pub(crate) fn parse_reg(s: &str) -> Option<uarch> {
+    reg::lookup(s)
+        .or_else(|| s.strip_prefix('r')?.parse().ok())
+        .filter(|&reg| (reg as usize) < reg::COUNT)
+}
+
+#[derive(Debug)]
+pub enum SymbolError {
+    BadLine(String),
+}
+
+impl Display for SymbolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::BadLine(line) => format!("Malformed symbol map entry `{}`", line),
+            }
+        )
+    }
+}
+
+impl Error for SymbolError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn symbolize() {
+        let mut syms: Symbols = "0x0002 _main\n\n0x000c _divide\n0d8 loop\n"
+            .parse()
+            .unwrap();
+        syms.insert("_start", 0x0000);
+        assert_eq!(syms.lookup("loop"), Some(0x0008));
+        assert_eq!(syms.lookup("_exit"), None);
+        assert_eq!(syms.resolve("_main"), Some(0x0002));
+        assert_eq!(syms.resolve("0x10"), Some(0x0010));
+        assert_eq!(syms.symbolize(0x0000), "0x0000 <_start>");
+        assert_eq!(syms.symbolize(0x000a), "0x000a <loop+2>");
+        assert_eq!(syms.name(0x0010).as_deref(), Some("_divide+4"));
+        assert_eq!(Symbols::new().symbolize(0x0004), "0x0004");
+        // Ensure bad maps are rejected
+        assert!(matches!(
+            "0x0002 _main extra".parse::<Symbols>(),
+            Err(SymbolError::BadLine(_))
+        ));
+        assert!(matches!(
+            "main 0x0002".parse::<Symbols>(),
+            Err(SymbolError::BadLine(_))
+        ));
+    }
+}
@@ -0,0 +1,133 @@
+//! System calls.
+//!
+//! The `sys` instruction hands control to the host through a [`SysHandler`].
+//! Arguments are passed in registers `R0`-`R3`, with the service number in
+//! `R0`. Results are returned in the same registers.
+
+use std::fmt::{self, Debug};
+use std::io::{self, Read, Stdin, Stdout, Write};
+
+use log::warn;
+
+use crate::uarch;
+
+/// Terminate execution with the exit code in `R1`.
+pub const EXIT: uarch = 0x0000;
+/// Write the character in `R1` to the console.
+pub const PUTCHAR: uarch = 0x0001;
+/// Read a character from the console into `R0`, or `0xffff` at end of input.
+pub const GETCHAR: uarch = 0x0002;
+/// Read the cycle counter into `R0`-`R3`, least significant word first.
+pub const CYCLES: uarch = 0x0003;
+
+/// Action for the processor to take once a system call returns.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Control {
+    /// Continue with the next instruction.
+    Continue,
+    /// Halt with the given exit code.
+    Exit(uarch),
+}
+
+/// Host-side implementation of system calls.
+pub trait SysHandler {
+    /// Services a system call.
+    ///
+    /// `args` holds registers `R0`-`R3`, which are written back to the
+    /// processor afterwards. `cycles` is the number of cycles executed so far.
+    fn sys(&mut self, args: &mut [uarch; 4], cycles: u64) -> Control;
+}
+
+impl Debug for dyn SysHandler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SysHandler")
+    }
+}
+
+impl Default for Box<dyn SysHandler> {
+    fn default() -> Self {
+        Box::new(Console::default())
+    }
+}
+
+/// Default system call handler, backed by a console.
+#[derive(Debug)]
+pub struct Console<R: Read, W: Write> {
+    input: R,
+    output: W,
+}
+
+impl<R: Read, W: Write> Console<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Self { input, output }
+    }
+}
+
+impl Default for Console<Stdin, Stdout> {
+    fn default() -> Self {
+        Self::new(io::stdin(), io::stdout())
+    }
+}
+
+impl<R: Read, W: Write> SysHandler for Console<R, W> {
+    fn sys(&mut self, args: &mut [uarch; 4], cycles: u64) -> Control {
+        match args[0] {
+            EXIT => return Control::Exit(args[1]),
+            PUTCHAR => {
+                let res = self
+                    .output
+                    .write_all(&[args[1] as u8])
+                    .and_then(|_| self.output.flush());
+                if let Err(err) = res {
+                    warn!("Could not write to console: {}", err);
+                }
+            }
+            GETCHAR => {
+                let mut buf = [0; 1];
+                args[0] = match self.input.read(&mut buf) {
+                    Ok(1) => buf[0] as uarch,
+                    Ok(_) => 0xffff,
+                    Err(err) => {
+                        warn!("Could not read from console: {}", err);
+                        0xffff
+                    }
+                };
+            }
+            CYCLES => {
+                for (i, arg) in args.iter_mut().enumerate() {
+                    *arg = (cycles >> (uarch::BITS as usize * i)) as uarch;
+                }
+            }
+            service => warn!("Unknown system call: {:#06x}", service),
+        }
+        Control::Continue
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn console() {
+        let mut console = Console::new(&b"k"[..], Vec::new());
+        // putchar
+        let mut args = [PUTCHAR, 'A' as uarch, 0, 0];
+        assert_eq!(console.sys(&mut args, 0), Control::Continue);
+        assert_eq!(console.output, b"A");
+        // getchar
+        let mut args = [GETCHAR, 0, 0, 0];
+        console.sys(&mut args, 0);
+        assert_eq!(args[0], 'k' as uarch);
+        let mut args = [GETCHAR, 0, 0, 0];
+        console.sys(&mut args, 0);
+        assert_eq!(args[0], 0xffff);
+        // cycles
+        let mut args = [CYCLES, 0, 0, 0];
+        console.sys(&mut args, 0x0123_4567_89ab_cdef);
+        assert_eq!(args, [0xcdef, 0x89ab, 0x4567, 0x0123]);
+        // exit
+        let mut args = [EXIT, 0x002a, 0, 0];
+        assert_eq!(console.sys(&mut args, 0), Control::Exit(0x002a));
+    }
+}
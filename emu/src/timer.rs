@@ -0,0 +1,131 @@
+//! Programmable timer.
+//!
+//! A countdown timer which requests an interrupt once a number of cycles have
+//! elapsed. It is controlled through three registers:
+//!
+//! | Offset   | Name     | Description                                  |
+//! | -------- | -------- | -------------------------------------------- |
+//! | `0x0000` | `COUNT`  | Cycles remaining until the timer expires     |
+//! | `0x0002` | `RELOAD` | Count to restart from on expiry (0 disables) |
+//! | `0x0004` | `CTRL`   | Control bits                                 |
+//!
+//! While enabled, `COUNT` is decremented every cycle. Upon reaching zero, the
+//! timer requests an interrupt and restarts from `RELOAD`. A one-shot timer,
+//! with a `RELOAD` of zero, disables itself instead.
+
+use std::ops::Range;
+
+use crate::bus::{self, Bus};
+use crate::{uarch, WORDSIZE};
+
+/// Addresses the emulator maps the timer at.
+pub const RANGE: Range<usize> = 0xffe0..0xffe6;
+
+/// Enables the timer when set in `CTRL`.
+pub const ENABLE: uarch = 0x0001;
+
+#[derive(Debug, Default)]
+pub struct Timer {
+    pub count: uarch,
+    pub reload: uarch,
+    pub ctrl: uarch,
+}
+
+impl Timer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Finds the register at an offset within the timer.
+    fn reg(&mut self, addr: uarch) -> Option<&mut uarch> {
+        if !(addr as usize).is_multiple_of(WORDSIZE) {
+            return None;
+        }
+        match addr as usize / WORDSIZE {
+            0 => Some(&mut self.count),
+            1 => Some(&mut self.reload),
+            2 => Some(&mut self.ctrl),
+            _ => None,
+        }
+    }
+}
+
+impl Bus for Timer {
+    fn read(&mut self, addr: uarch) -> Option<uarch> {
+        self.peek(addr)
+    }
+
+    fn write(&mut self, addr: uarch, word: uarch) -> Option<()> {
+        *self.reg(addr)? = word;
+        Some(())
+    }
+
+    fn peek(&self, addr: uarch) -> Option<uarch> {
+        if !(addr as usize).is_multiple_of(WORDSIZE) {
+            return None;
+        }
+        [self.count, self.reload, self.ctrl]
+            .get(addr as usize / WORDSIZE)
+            .copied()
+    }
+
+    fn save(&self) -> Vec<u8> {
+        bus::save_words(&[self.count, self.reload, self.ctrl])
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Option<()> {
+        [self.count, self.reload, self.ctrl] = bus::restore_words(state)?;
+        Some(())
+    }
+
+    fn tick(&mut self) -> bool {
+        if self.ctrl & ENABLE == 0 || self.count == 0 {
+            return false;
+        }
+        self.count -= 1;
+        if self.count != 0 {
+            return false;
+        }
+        // Restart, or stop if one-shot
+        self.count = self.reload;
+        if self.reload == 0 {
+            self.ctrl &= !ENABLE;
+        }
+        true
+    }
+
+    fn busy(&self) -> bool {
+        self.ctrl & ENABLE != 0 && self.count != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn countdown() {
+        let mut timer = Timer::new();
+        timer.write(0x0000, 0x0002).unwrap();
+        timer.write(0x0002, 0x0003).unwrap();
+        // Ensure nothing happens until enabled
+        assert!(!timer.tick());
+        assert_eq!(timer.read(0x0000), Some(0x0002));
+        timer.write(0x0004, ENABLE).unwrap();
+        // Ensure the timer expires, then reloads
+        assert!(!timer.tick());
+        assert!(timer.tick());
+        assert_eq!(timer.read(0x0000), Some(0x0003));
+        assert!(!timer.tick());
+        assert!(!timer.tick());
+        assert!(timer.tick());
+        // Ensure one-shot timers disable themselves
+        timer.write(0x0002, 0x0000).unwrap();
+        assert!(!timer.tick());
+        assert!(!timer.tick());
+        assert!(timer.tick());
+        assert_eq!(timer.read(0x0004), Some(0x0000));
+        assert!(!timer.tick());
+        assert_eq!(timer.read(0x0006), None);
+    }
+}
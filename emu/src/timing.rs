@@ -0,0 +1,212 @@
+//! Instruction timing.
+//!
+//! Each instruction takes a number of cycles to execute, as given by a
+//! [`Timing`] table. Taken branches cost extra cycles on top of their base
+//! cost, as do other instructions which write the PC, such as `mov pc, lr`.
+//! `rti` always returns, so its base cost already includes the branch.
+//!
+//! Tables can be loaded from a file, where each line holds a core
+//! instruction's mnemonic followed by its cost in cycles. The extra cost of a
+//! taken branch is named `taken`. Instructions which are not listed keep their
+//! default cost, and `#` starts a comment:
+//!
+//! ```text
+//! # Slow multiplier
+//! mul   8
+//! taken 2
+//! ```
+
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use isa::inst::{Add, And, Ldr, Mov, Mul, Orr, Shf, Sub, Xor};
+use isa::Instruction;
+
+use crate::uarch;
+
+/// Core instructions, in the order of their costs.
+const NAMES: [&str; 16] = [
+    "add", "and", "bra", "cmp", "hlt", "iff", "ldr", "mov", "mul", "orr", "rti", "shf", "str",
+    "sub", "sys", "xor",
+];
+
+/// Default cost of each core instruction.
+const COSTS: [u64; 16] = [1, 1, 1, 1, 1, 1, 2, 1, 3, 1, 2, 1, 2, 1, 1, 1];
+
+/// Default extra cost of a taken branch.
+const TAKEN: u64 = 1;
+
+/// Cost of each instruction, in cycles.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Timing {
+    costs: [u64; 16],
+    taken: u64,
+}
+
+impl Default for Timing {
+    fn default() -> Self {
+        Self {
+            costs: COSTS,
+            taken: TAKEN,
+        }
+    }
+}
+
+impl Timing {
+    /// Loads a table from a file, starting from the default costs.
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Ok(fs::read_to_string(path)?.parse()?)
+    }
+
+    /// Number of cycles taken to execute an instruction.
+    ///
+    /// The status register is needed to determine if branches are taken.
+    pub fn cost(&self, instr: &Instruction, sr: uarch) -> u64 {
+        let cost = self.costs[index(instr)];
+        match instr {
+            Instruction::Bra(bra) if bra.cond.eval(sr) => cost + self.taken,
+            instr if dest(instr) == Some(15) => cost + self.taken,
+            _ => cost,
+        }
+    }
+
+    /// Sets the cost of an instruction, or of taken branches.
+    ///
+    /// Returns `None` if the name is unknown.
+    pub fn set(&mut self, name: &str, cost: u64) -> Option<()> {
+        match name {
+            "taken" => self.taken = cost,
+            name => {
+                let idx = NAMES.iter().position(|&other| other == name)?;
+                self.costs[idx] = cost;
+            }
+        }
+        Some(())
+    }
+}
+
+impl FromStr for Timing {
+    type Err = TimingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut timing = Self::default();
+        for line in s.lines() {
+            // Strip comments
+            let line = line.split('#').next().unwrap_or_default();
+            match line.split_whitespace().collect::<Vec<_>>()[..] {
+                [] => continue,
+                [name, cost] => {
+                    let cost = cost
+                        .parse()
+                        .map_err(|_| TimingError::BadLine(line.trim().to_string()))?;
+                    timing
+                        .set(name, cost)
+                        .ok_or_else(|| TimingError::Unknown(name.to_string()))?;
+                }
+                _ => return Err(TimingError::BadLine(line.trim().to_string())),
+            }
+        }
+        Ok(timing)
+    }
+}
+
+/// Finds the index of an instruction's cost.
+fn index(instr: &Instruction) -> usize {
+    match instr {
+        Instruction::Add(_) => 0,
+        Instruction::And(_) => 1,
+        Instruction::Bra(_) => 2,
+        Instruction::Cmp(_) => 3,
+        Instruction::Hlt(_) => 4,
+        Instruction::Iff(_) => 5,
+        Instruction::Ldr(_) => 6,
+        Instruction::Mov(_) => 7,
+        Instruction::Mul(_) => 8,
+        Instruction::Orr(_) => 9,
+        Instruction::Rti(_) => 10,
+        Instruction::Shf(_) => 11,
+        Instruction::Str(_) => 12,
+        Instruction::Sub(_) => 13,
+        Instruction::Sys(_) => 14,
+        Instruction::Xor(_) => 15,
+    }
+}
+
+/// Finds the register an instruction writes its result to, if any.
+fn dest(instr: &Instruction) -> Option<uarch> {
+    match *instr {
+        Instruction::Add(Add { op1, .. })
+        | Instruction::And(And { op1, .. })
+        | Instruction::Ldr(Ldr { op1, .. })
+        | Instruction::Mov(Mov { op1, .. })
+        | Instruction::Mul(Mul { op1, .. })
+        | Instruction::Orr(Orr { op1, .. })
+        | Instruction::Shf(Shf { op1, .. })
+        | Instruction::Sub(Sub { op1, .. })
+        | Instruction::Xor(Xor { op1, .. }) => Some(op1),
+        _ => None,
+    }
+}
+
+#[derive(Debug)]
+pub enum TimingError {
+    BadLine(String),
+    Unknown(String),
+}
+
+impl Display for TimingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::BadLine(line) => format!("Malformed timing entry `{}`", line),
+                Self::Unknown(name) => format!("Unknown instruction `{}`", name),
+            }
+        )
+    }
+}
+
+impl Error for TimingError {}
+
+#[cfg(test)]
+mod tests {
+    use isa::Encoding;
+
+    use super::*;
+
+    #[test]
+    fn cost() {
+        let timing: Timing = "# Slow multiplier\nmul 8\n\ntaken 2 # extra\n"
+            .parse()
+            .unwrap();
+        let decode = |word| Instruction::decode(word, Encoding::V1).unwrap();
+        // add r0, 0x1
+        assert_eq!(timing.cost(&decode(0xc081), 0x0000), 1);
+        // mul r0, 0x1
+        assert_eq!(timing.cost(&decode(0x6081), 0x0000), 8);
+        // ldr r0, r1
+        assert_eq!(timing.cost(&decode(0x3001), 0x0000), 2);
+        // goto +0x0
+        assert_eq!(timing.cost(&decode(0x0080), 0x0000), 3);
+        // mov pc, lr; pop pc
+        assert_eq!(timing.cost(&decode(0x7f0e), 0x0000), 3);
+        assert_eq!(timing.cost(&decode(0x3f40), 0x0000), 4);
+        // beq +0x0
+        let beq = Instruction::decode(0xf180, Encoding::V0).unwrap();
+        assert_eq!(timing.cost(&beq, 0x0000), 1);
+        assert_eq!(timing.cost(&beq, 0x0001), 3);
+        // Ensure bad tables are rejected
+        assert!(matches!(
+            "frob 1".parse::<Timing>(),
+            Err(TimingError::Unknown(_))
+        ));
+        assert!(matches!(
+            "mul x".parse::<Timing>(),
+            Err(TimingError::BadLine(_))
+        ));
+    }
+}
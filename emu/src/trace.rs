@@ -0,0 +1,410 @@
+//! Execution traces.
+//!
+//! A [`Tracer`] writes a [`Record`] for each instruction executed, in either
+//! of two formats chosen by the file's extension: JSON Lines (`.jsonl`), with
+//! one object per line, or CSV (`.csv`), with a header line.
+//!
+//! | Field       | Contents                                               |
+//! | ----------- | ------------------------------------------------------ |
+//! | `cycle`     | number of cycles executed before the instruction       |
+//! | `pc`        | address of the instruction                             |
+//! | `word`      | instruction word, if it could be fetched               |
+//! | `disasm`    | disassembly of the instruction word                    |
+//! | `regs`      | registers changed by the instruction, other than `pc`  |
+//! | `sr_before` | status register before the instruction                 |
+//! | `sr_after`  | status register after the instruction                  |
+//! | `writes`    | writes made to memory                                  |
+//!
+//! In CSV, words are written in hex. Changed registers are written as
+//! `name=value` and writes as `addr:old->new`, each separated by `;`:
+//!
+//! ```text
+//! cycle,pc,word,disasm,regs,sr_before,sr_after,writes
+//! 3,0x0006,0x2d40,push g0,sp=0x3ffe,0x0000,0x0000,0x3ffe:0x0000->0x0020
+//! ```
+//!
+//! When an interrupt is taken, the record of the handler's first instruction
+//! holds the status register from before the interrupt.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+use isa::{reg, Instruction};
+use serde::{Deserialize, Serialize};
+
+use crate::bus::Bus;
+use crate::proc::Processor;
+use crate::reg::Register;
+use crate::sym::parse_num;
+use crate::{uarch, BANKSIZE};
+
+const HEADER: &str = "cycle,pc,word,disasm,regs,sr_before,sr_after,writes";
+
+/// Trace of a single instruction.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    pub cycle: u64,
+    pub pc: uarch,
+    pub word: Option<uarch>,
+    pub disasm: String,
+    /// New values of the changed registers, by name.
+    pub regs: BTreeMap<String, uarch>,
+    pub sr_before: uarch,
+    pub sr_after: uarch,
+    pub writes: Vec<Store>,
+}
+
+/// A write to memory.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Store {
+    pub addr: uarch,
+    pub old: uarch,
+    pub new: uarch,
+}
+
+impl Record {
+    /// Captures the trace of the instruction a processor just executed.
+    ///
+    /// Takes the registers, status register and cycle count from before the
+    /// instruction.
+    pub(crate) fn capture(
+        proc: &Processor,
+        regs: &[Register; BANKSIZE],
+        sr: uarch,
+        cycle: u64,
+    ) -> Self {
+        let pc = proc.last;
+        let word = proc.bus.peek(pc);
+        let disasm = match word.map(|word| Instruction::decode(word, proc.enc)) {
+            Some(Ok(instr)) => instr.to_string(),
+            Some(Err(_)) => "(undefined)".to_string(),
+            None => "(unreadable)".to_string(),
+        };
+        Self {
+            cycle,
+            pc,
+            word,
+            disasm,
+            regs: (0..15)
+                .filter(|&idx| *regs[idx] != *proc.regs[idx as uarch])
+                .map(|idx| {
+                    (
+                        reg::name(idx as uarch).to_string(),
+                        *proc.regs[idx as uarch],
+                    )
+                })
+                .collect(),
+            sr_before: sr,
+            sr_after: *proc.sr,
+            writes: proc
+                .writes
+                .iter()
+                .map(|&(addr, old, new)| Store { addr, old, new })
+                .collect(),
+        }
+    }
+
+    /// Names the fields which differ from another record.
+    pub fn diff(&self, other: &Self) -> Vec<&'static str> {
+        [
+            ("cycle", self.cycle == other.cycle),
+            ("pc", self.pc == other.pc),
+            ("word", self.word == other.word),
+            ("disasm", self.disasm == other.disasm),
+            ("regs", self.regs == other.regs),
+            ("sr_before", self.sr_before == other.sr_before),
+            ("sr_after", self.sr_after == other.sr_after),
+            ("writes", self.writes == other.writes),
+        ]
+        .into_iter()
+        .filter(|&(_, same)| !same)
+        .map(|(field, _)| field)
+        .collect()
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("record should serialize")
+    }
+
+    pub fn from_json(line: &str) -> Result<Self, TraceError> {
+        serde_json::from_str(line).map_err(|_| TraceError::BadRecord(line.to_string()))
+    }
+
+    pub fn to_csv(&self) -> String {
+        let regs: Vec<_> = self
+            .regs
+            .iter()
+            .map(|(name, value)| format!("{}={:#06x}", name, value))
+            .collect();
+        let writes: Vec<_> = self
+            .writes
+            .iter()
+            .map(|store| format!("{:#06x}:{:#06x}->{:#06x}", store.addr, store.old, store.new))
+            .collect();
+        format!(
+            "{},{:#06x},{},{},{},{:#06x},{:#06x},{}",
+            self.cycle,
+            self.pc,
+            self.word
+                .map_or_else(String::new, |word| format!("{:#06x}", word)),
+            quote(&self.disasm),
+            regs.join(";"),
+            self.sr_before,
+            self.sr_after,
+            writes.join(";"),
+        )
+    }
+
+    pub fn from_csv(line: &str) -> Result<Self, TraceError> {
+        Self::parse_csv(line).ok_or_else(|| TraceError::BadRecord(line.to_string()))
+    }
+
+    fn parse_csv(line: &str) -> Option<Self> {
+        let fields = split(line)?;
+        let [cycle, pc, word, disasm, regs, sr_before, sr_after, writes] = &fields[..] else {
+            return None;
+        };
+        Some(Self {
+            cycle: cycle.parse().ok()?,
+            pc: parse_num(pc)?,
+            word: match word.as_str() {
+                "" => None,
+                word => Some(parse_num(word)?),
+            },
+            disasm: disasm.clone(),
+            regs: regs
+                .split(';')
+                .filter(|reg| !reg.is_empty())
+                .map(|reg| {
+                    let (name, value) = reg.split_once('=')?;
+                    Some((name.to_string(), parse_num(value)?))
+                })
+                .collect::<Option<_>>()?,
+            sr_before: parse_num(sr_before)?,
+            sr_after: parse_num(sr_after)?,
+            writes: writes
+                .split(';')
+                .filter(|store| !store.is_empty())
+                .map(|store| {
+                    let (addr, rest) = store.split_once(':')?;
+                    let (old, new) = rest.split_once("->")?;
+                    Some(Store {
+                        addr: parse_num(addr)?,
+                        old: parse_num(old)?,
+                        new: parse_num(new)?,
+                    })
+                })
+                .collect::<Option<_>>()?,
+        })
+    }
+}
+
+/// Quotes a CSV field if needed.
+fn quote(field: &str) -> String {
+    match field.contains([',', '"']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string(),
+    }
+}
+
+/// Splits a line of CSV into its fields.
+fn split(line: &str) -> Option<Vec<String>> {
+    let mut fields = vec![String::new()];
+    let mut chars = line.chars().peekable();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        let field = fields.last_mut()?;
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            ('"', _) => quoted = !quoted,
+            (',', false) => fields.push(String::new()),
+            (c, _) => field.push(c),
+        }
+    }
+    (!quoted).then_some(fields)
+}
+
+/// Formats a trace can be written in.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Format {
+    Jsonl,
+    Csv,
+}
+
+impl Format {
+    /// Chooses a format by a file's extension.
+    pub fn from_path(path: &Path) -> Result<Self, TraceError> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("jsonl") => Ok(Self::Jsonl),
+            Some("csv") => Ok(Self::Csv),
+            _ => Err(TraceError::BadFormat),
+        }
+    }
+}
+
+/// Writes a record of each instruction executed.
+pub struct Tracer {
+    out: Box<dyn Write>,
+    format: Format,
+    /// First error encountered while writing.
+    err: Option<io::Error>,
+}
+
+impl Tracer {
+    pub fn new(out: impl Write + 'static, format: Format) -> Self {
+        let mut tracer = Self {
+            out: Box::new(out),
+            format,
+            err: None,
+        };
+        if format == Format::Csv {
+            tracer.write(HEADER);
+        }
+        tracer
+    }
+
+    pub(crate) fn record(&mut self, rec: &Record) {
+        let line = match self.format {
+            Format::Jsonl => rec.to_json(),
+            Format::Csv => rec.to_csv(),
+        };
+        self.write(&line);
+    }
+
+    /// Writes a line, unless writing has already failed.
+    fn write(&mut self, line: &str) {
+        if self.err.is_none() {
+            self.err = writeln!(self.out, "{}", line).err();
+        }
+    }
+
+    /// Flushes the trace, returning the first error encountered.
+    pub fn finish(mut self) -> io::Result<()> {
+        match self.err.take() {
+            Some(err) => Err(err),
+            None => self.out.flush(),
+        }
+    }
+}
+
+/// Reads the records of a trace.
+pub fn read(
+    input: impl BufRead,
+    format: Format,
+) -> impl Iterator<Item = Result<Record, Box<dyn Error>>> {
+    input
+        .lines()
+        .enumerate()
+        // Skip the CSV header
+        .filter(move |(idx, _)| !(format == Format::Csv && *idx == 0))
+        .map(move |(_, line)| {
+            let line = line?;
+            Ok(match format {
+                Format::Jsonl => Record::from_json(&line)?,
+                Format::Csv => Record::from_csv(&line)?,
+            })
+        })
+}
+
+#[derive(Debug)]
+pub enum TraceError {
+    BadFormat,
+    BadRecord(String),
+}
+
+impl Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::BadFormat => "Trace files must end in `.jsonl` or `.csv`".to_string(),
+                Self::BadRecord(line) => format!("Malformed trace record `{}`", line),
+            }
+        )
+    }
+}
+
+impl Error for TraceError {}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+    use std::io::BufReader;
+
+    use super::*;
+    use crate::Emulator;
+
+    #[test]
+    fn trace() {
+        let path = std::env::temp_dir().join(format!("trace-{}.csv", std::process::id()));
+        let mut e = Emulator::new();
+        // .word 0x0002; mov sp, 0x30; mov r0, 0x5; push r0; hlt
+        let rom = [0x02, 0x00, 0xb0, 0x7d, 0x85, 0x70, 0x40, 0x20, 0x00, 0x0c];
+        e.load_bytes(&rom).unwrap();
+        e.set_tracer(Some(Tracer::new(File::create(&path).unwrap(), Format::Csv)));
+        e.run();
+        e.set_tracer(None).unwrap().finish().unwrap();
+        let recs = read(BufReader::new(File::open(&path).unwrap()), Format::Csv)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        fs::remove_file(path).unwrap();
+        // Ensure each instruction's effects are recorded
+        assert_eq!(recs.len(), 4);
+        assert_eq!(recs[1].disasm, "mov a0, 0x0005");
+        assert_eq!(recs[1].regs, BTreeMap::from([("a0".to_string(), 0x0005)]));
+        assert_eq!(recs[2].pc, 0x0006);
+        assert_eq!(recs[2].regs, BTreeMap::from([("sp".to_string(), 0x002e)]));
+        assert_eq!(
+            recs[2].writes,
+            [Store {
+                addr: 0x002e,
+                old: 0x0000,
+                new: 0x0005,
+            }]
+        );
+        assert_eq!(recs[3].cycle, e.cycles() - 1);
+    }
+
+    #[test]
+    fn roundtrip() {
+        let rec = Record {
+            cycle: 3,
+            pc: 0x0006,
+            word: Some(0x2d40),
+            disasm: "push g0".to_string(),
+            regs: BTreeMap::from([("sp".to_string(), 0x3ffe)]),
+            sr_before: 0x0000,
+            sr_after: 0x0001,
+            writes: vec![Store {
+                addr: 0x3ffe,
+                old: 0x0000,
+                new: 0x0020,
+            }],
+        };
+        assert_eq!(
+            rec.to_csv(),
+            "3,0x0006,0x2d40,push g0,sp=0x3ffe,0x0000,0x0001,0x3ffe:0x0000->0x0020"
+        );
+        assert_eq!(Record::from_csv(&rec.to_csv()).unwrap(), rec);
+        assert_eq!(Record::from_json(&rec.to_json()).unwrap(), rec);
+        // Ensure fields with commas are quoted
+        let other = Record {
+            word: None,
+            disasm: "mov a0, g0".to_string(),
+            regs: BTreeMap::new(),
+            writes: Vec::new(),
+            ..rec.clone()
+        };
+        assert_eq!(other.to_csv(), "3,0x0006,,\"mov a0, g0\",,0x0000,0x0001,");
+        assert_eq!(Record::from_csv(&other.to_csv()).unwrap(), other);
+        assert_eq!(rec.diff(&other), ["word", "disasm", "regs", "writes"]);
+        assert!(Record::from_csv("3,0x0006").is_err());
+    }
+}
@@ -0,0 +1,389 @@
+//! Serial console.
+//!
+//! A UART which transmits and receives bytes over a port on the host. It is
+//! controlled through three registers:
+//!
+//! | Offset   | Name     | Description                                  |
+//! | -------- | -------- | -------------------------------------------- |
+//! | `0x0000` | `DATA`   | Received byte when read, transmits written   |
+//! | `0x0002` | `STATUS` | Status bits (read-only)                      |
+//! | `0x0004` | `CTRL`   | Control bits                                 |
+//!
+//! `STATUS` sets [`RX_READY`] while a received byte is waiting to be read from
+//! `DATA`, and [`TX_READY`] while a byte written to `DATA` can be transmitted.
+//! Bytes received while one is waiting are held by the port until it is read.
+//! While [`RX_INT`] is set in `CTRL`, the UART requests an interrupt whenever a
+//! byte is waiting.
+//!
+//! The port is only read from once the program first accesses the UART, so
+//! that programs which never use it do not consume its input.
+
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::net::TcpListener;
+use std::ops::Range;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+use log::{info, warn};
+
+use crate::bus::{self, Bus};
+use crate::{uarch, WORDSIZE};
+
+/// Addresses the emulator maps the UART at.
+pub const RANGE: Range<usize> = 0xffd8..0xffde;
+
+/// Set in `STATUS` while a received byte is waiting.
+pub const RX_READY: uarch = 0x0001;
+/// Set in `STATUS` while bytes can be transmitted.
+pub const TX_READY: uarch = 0x0002;
+/// Enables interrupts on receiving when set in `CTRL`.
+pub const RX_INT: uarch = 0x0001;
+
+#[derive(Default)]
+pub struct Uart {
+    /// Received byte waiting to be read.
+    rx: Option<u8>,
+    ctrl: uarch,
+    port: Option<Port>,
+}
+
+/// A connection to the host.
+struct Port {
+    input: Input,
+    output: Box<dyn Write>,
+}
+
+enum Input {
+    /// Not yet read from.
+    Idle(Box<dyn Read + Send>),
+    /// Read from by a background thread.
+    Receiving(Receiver<u8>),
+    /// Reached the end of input.
+    Closed,
+}
+
+impl Input {
+    /// Starts receiving, if not already.
+    fn start(&mut self) {
+        match std::mem::replace(self, Self::Closed) {
+            Self::Idle(mut input) => {
+                // Read on another thread, so that execution never blocks on
+                // input
+                let (tx, rx) = mpsc::channel();
+                thread::spawn(move || {
+                    let mut buf = [0; 1];
+                    loop {
+                        match input.read(&mut buf) {
+                            Ok(1) if tx.send(buf[0]).is_ok() => (),
+                            Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+                            Err(err) => {
+                                warn!("Could not read from UART: {}", err);
+                                break;
+                            }
+                            _ => break,
+                        }
+                    }
+                });
+                *self = Self::Receiving(rx);
+            }
+            input => *self = input,
+        }
+    }
+}
+
+impl Uart {
+    /// Creates a UART which is not connected to any port.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a UART which receives from and transmits to a port.
+    pub fn connect(input: impl Read + Send + 'static, output: impl Write + 'static) -> Self {
+        Self {
+            port: Some(Port {
+                input: Input::Idle(Box::new(input)),
+                output: Box::new(output),
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// Opens a port on the host, waiting for a client to connect if needed:
+    ///
+    /// - `stdio` connects to the standard input and output.
+    /// - `file:PATH` transmits to a file, and never receives.
+    /// - `pty` connects to a new pseudo-terminal.
+    /// - `tcp:PORT` connects to a client of a TCP port on the local host.
+    /// - `none` leaves the UART unconnected.
+    pub fn open(port: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(match port.split_once(':') {
+            None if port == "stdio" => Self::connect(io::stdin(), io::stdout()),
+            None if port == "none" => Self::new(),
+            None if port == "pty" => {
+                let (master, path) = pty::open()?;
+                info!("UART connected to {}", path);
+                Self::connect(master.try_clone()?, master)
+            }
+            Some(("file", path)) => Self::connect(io::empty(), File::create(path)?),
+            Some(("tcp", port)) => {
+                let port = port
+                    .parse::<u16>()
+                    .map_err(|_| UartError::BadPort(port.to_string()))?;
+                let listener = TcpListener::bind(("127.0.0.1", port))?;
+                info!("UART listening on {}", listener.local_addr()?);
+                let (stream, _) = listener.accept()?;
+                Self::connect(stream.try_clone()?, stream)
+            }
+            _ => return Err(UartError::BadPort(port.to_string()).into()),
+        })
+    }
+
+    /// Transmits a byte.
+    fn transmit(&mut self, byte: u8) {
+        let Some(port) = &mut self.port else {
+            return;
+        };
+        let res = port
+            .output
+            .write_all(&[byte])
+            .and_then(|_| port.output.flush());
+        if let Err(err) = res {
+            warn!("Could not write to UART: {}", err);
+        }
+    }
+
+    fn status(&self) -> uarch {
+        let mut status = 0;
+        if self.rx.is_some() {
+            status |= RX_READY;
+        }
+        if self.port.is_some() {
+            status |= TX_READY;
+        }
+        status
+    }
+}
+
+impl Bus for Uart {
+    fn read(&mut self, addr: uarch) -> Option<uarch> {
+        if let Some(port) = &mut self.port {
+            port.input.start();
+        }
+        let word = self.peek(addr)?;
+        if addr == 0x0000 {
+            self.rx = None;
+        }
+        Some(word)
+    }
+
+    fn write(&mut self, addr: uarch, word: uarch) -> Option<()> {
+        if let Some(port) = &mut self.port {
+            port.input.start();
+        }
+        match addr {
+            0x0000 => self.transmit(word as u8),
+            0x0004 => self.ctrl = word,
+            _ => return None,
+        }
+        Some(())
+    }
+
+    fn peek(&self, addr: uarch) -> Option<uarch> {
+        if !(addr as usize).is_multiple_of(WORDSIZE) {
+            return None;
+        }
+        [self.rx.unwrap_or(0) as uarch, self.status(), self.ctrl]
+            .get(addr as usize / WORDSIZE)
+            .copied()
+    }
+
+    fn tick(&mut self) -> bool {
+        if let (None, Some(port)) = (self.rx, &mut self.port) {
+            if let Input::Receiving(rx) = &port.input {
+                match rx.try_recv() {
+                    Ok(byte) => self.rx = Some(byte),
+                    Err(TryRecvError::Empty) => (),
+                    Err(TryRecvError::Disconnected) => port.input = Input::Closed,
+                }
+            }
+        }
+        self.rx.is_some() && self.ctrl & RX_INT != 0
+    }
+
+    fn busy(&self) -> bool {
+        let receiving = |port: &Port| matches!(port.input, Input::Receiving(_));
+        self.rx.is_none() && self.port.as_ref().is_some_and(receiving)
+    }
+
+    fn save(&self) -> Vec<u8> {
+        // Flag whether a byte is waiting above the byte itself
+        let rx = self.rx.map_or(0, |byte| 0x0100 | byte as uarch);
+        bus::save_words(&[rx, self.ctrl])
+    }
+
+    fn restore(&mut self, state: &[u8]) -> Option<()> {
+        let [rx, ctrl] = bus::restore_words(state)?;
+        self.rx = (rx & 0x0100 != 0).then_some(rx as u8);
+        self.ctrl = ctrl;
+        Some(())
+    }
+}
+
+/// Pseudo-terminals.
+mod pty {
+    use std::ffi::CStr;
+    use std::fs::File;
+    use std::io::{self, Read, Write};
+    use std::os::unix::io::FromRawFd;
+
+    /// The master side of a pseudo-terminal.
+    pub struct Master {
+        file: File,
+        /// Slave side, held open so that reads do not fail before a client
+        /// opens it.
+        _slave: File,
+    }
+
+    impl Master {
+        pub fn try_clone(&self) -> io::Result<Self> {
+            Ok(Self {
+                file: self.file.try_clone()?,
+                _slave: self._slave.try_clone()?,
+            })
+        }
+    }
+
+    impl Read for Master {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.file.read(buf)
+        }
+    }
+
+    impl Write for Master {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.file.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.file.flush()
+        }
+    }
+
+    /// Checks the result of a libc call.
+    fn check(res: libc::c_int) -> io::Result<libc::c_int> {
+        match res {
+            -1 => Err(io::Error::last_os_error()),
+            res => Ok(res),
+        }
+    }
+
+    /// Opens a new pseudo-terminal in raw mode, returning its master side and
+    /// the path of its slave side.
+    pub fn open() -> io::Result<(Master, String)> {
+        // SAFETY: Each call is checked for failure, and the master's file
+        // descriptor is owned by the returned file.
+        unsafe {
+            let fd = check(libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY))?;
+            let file = File::from_raw_fd(fd);
+            check(libc::grantpt(fd))?;
+            check(libc::unlockpt(fd))?;
+            let mut buf = [0; 64];
+            let res = libc::ptsname_r(fd, buf.as_mut_ptr(), buf.len());
+            if res != 0 {
+                return Err(io::Error::from_raw_os_error(res));
+            }
+            let path = CStr::from_ptr(buf.as_ptr()).to_string_lossy().into_owned();
+            // Pass bytes through unmodified
+            let mut term = std::mem::zeroed();
+            check(libc::tcgetattr(fd, &mut term))?;
+            libc::cfmakeraw(&mut term);
+            check(libc::tcsetattr(fd, libc::TCSANOW, &term))?;
+            let slave = File::options().read(true).write(true).open(&path)?;
+            Ok((
+                Master {
+                    file,
+                    _slave: slave,
+                },
+                path,
+            ))
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum UartError {
+    BadPort(String),
+}
+
+impl Display for UartError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::BadPort(port) => format!("Unknown UART port `{}`", port),
+            }
+        )
+    }
+}
+
+impl Error for UartError {}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    /// Output shared with the test.
+    #[derive(Clone, Default)]
+    struct Capture(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn echo() {
+        let output = Capture::default();
+        let mut uart = Uart::connect(&b"hi"[..], output.clone());
+        // Ensure nothing is received until enabled
+        assert!(!uart.tick());
+        assert_eq!(uart.peek(0x0002), Some(TX_READY));
+        uart.write(0x0004, RX_INT).unwrap();
+        // Wait for the first byte to arrive
+        let start = Instant::now();
+        while !uart.tick() {
+            assert!(start.elapsed() < Duration::from_secs(5));
+        }
+        assert_eq!(uart.read(0x0002), Some(RX_READY | TX_READY));
+        assert_eq!(uart.read(0x0000), Some('h' as uarch));
+        assert_eq!(uart.peek(0x0002), Some(TX_READY));
+        // Ensure state is restored
+        while !uart.tick() {
+            assert!(start.elapsed() < Duration::from_secs(5));
+        }
+        let mut other = Uart::new();
+        other.restore(&uart.save()).unwrap();
+        assert_eq!(other.peek(0x0000), Some('i' as uarch));
+        assert_eq!(other.peek(0x0002), Some(RX_READY));
+        assert_eq!(other.peek(0x0004), Some(RX_INT));
+        // Ensure bytes are transmitted
+        uart.write(0x0000, 'k' as uarch).unwrap();
+        assert_eq!(*output.0.borrow(), b"k");
+        assert_eq!(uart.write(0x0002, 0x0000), None);
+        assert_eq!(uart.read(0x0006), None);
+        assert!(Uart::open("serial").is_err());
+    }
+}
@@ -0,0 +1,43 @@
+//! Memory watchpoints.
+//!
+//! A watchpoint stops execution once an instruction loads from or stores to a
+//! range of addresses. Only accesses made by `ldr` and `str`, including `push`
+//! and `pop`, are watched; instruction fetches are not.
+
+use std::ops::Range;
+
+use crate::uarch;
+
+/// Kinds of access a watchpoint stops on.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Watch {
+    Read,
+    Write,
+    /// Either a read or a write.
+    Access,
+}
+
+impl Watch {
+    /// Checks if an access is watched.
+    pub fn matches(self, write: bool) -> bool {
+        match self {
+            Self::Read => !write,
+            Self::Write => write,
+            Self::Access => true,
+        }
+    }
+}
+
+/// A watched range of addresses.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Watchpoint {
+    pub range: Range<usize>,
+    pub kind: Watch,
+}
+
+impl Watchpoint {
+    /// Checks if an access to an address is watched.
+    pub fn matches(&self, addr: uarch, write: bool) -> bool {
+        self.range.contains(&(addr as usize)) && self.kind.matches(write)
+    }
+}
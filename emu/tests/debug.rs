@@ -0,0 +1,42 @@
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[test]
+fn getchar() {
+    // mov r0, 0x2; sys; hlt
+    let rom = env::temp_dir().join(format!("emu-debug-{}.rom", std::process::id()));
+    fs::write(&rom, [0x02, 0x00, 0x82, 0x70, 0x00, 0x08, 0x00, 0x0c]).unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_emu"))
+        .arg("--debug")
+        .arg(&rom)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    // Step up to the call, then let it read `k` before continuing
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"s\ns\nkc\n")
+        .unwrap();
+    // Ensure the program reads its input without deadlocking
+    let start = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            break status;
+        }
+        if start.elapsed() > Duration::from_secs(10) {
+            child.kill().unwrap();
+            panic!("debugger deadlocked reading from stdin");
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+    fs::remove_file(&rom).unwrap();
+    assert_eq!(status.code(), Some('k' as i32));
+}
@@ -0,0 +1,115 @@
+//! Condition codes.
+//!
+//! Conditions are tested against the status register's flags, and are shared
+//! by every instruction which executes conditionally.
+
+use crate::uarch;
+
+/// Condition under which an instruction takes effect.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Cond {
+    /// Always
+    Al = 0b0000,
+    /// Never
+    Nv = 0b0001,
+    /// Equal (`Z`)
+    Eq = 0b0010,
+    /// Not equal (`!Z`)
+    Ne = 0b0011,
+    /// Less than (`N != V`)
+    Lt = 0b0100,
+    /// Less than or equal (`Z | N != V`)
+    Le = 0b0101,
+    /// Greater than or equal (`N == V`)
+    Ge = 0b0110,
+    /// Greater than (`!Z & N == V`)
+    Gt = 0b0111,
+    /// Carry clear (`!C`)
+    Cc = 0b1000,
+    /// Carry set (`C`)
+    Cs = 0b1001,
+    /// Overflow clear (`!V`)
+    Vc = 0b1010,
+    /// Overflow set (`V`)
+    Vs = 0b1011,
+    /// Plus (`!N`)
+    Pl = 0b1100,
+    /// Minus (`N`)
+    Mi = 0b1101,
+}
+
+impl Cond {
+    /// Every condition, ordered by its encoding.
+    pub const ALL: [Self; 14] = [
+        Self::Al,
+        Self::Nv,
+        Self::Eq,
+        Self::Ne,
+        Self::Lt,
+        Self::Le,
+        Self::Ge,
+        Self::Gt,
+        Self::Cc,
+        Self::Cs,
+        Self::Vc,
+        Self::Vs,
+        Self::Pl,
+        Self::Mi,
+    ];
+
+    /// Evaluates this condition against the status register.
+    pub fn eval(self, sr: uarch) -> bool {
+        let zero = (sr & 0x0001) != 0;
+        let negative = (sr & 0x0002) != 0;
+        let overflow = (sr & 0x0004) != 0;
+        let carry = (sr & 0x0008) != 0;
+        match self {
+            Self::Al => true,
+            Self::Nv => false,
+            Self::Eq => zero,
+            Self::Ne => !zero,
+            Self::Lt => negative != overflow,
+            Self::Le => zero || negative != overflow,
+            Self::Ge => negative == overflow,
+            Self::Gt => !zero && negative == overflow,
+            Self::Cc => !carry,
+            Self::Cs => carry,
+            Self::Vc => !overflow,
+            Self::Vs => overflow,
+            Self::Pl => !negative,
+            Self::Mi => negative,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encoding() {
+        for (i, cond) in Cond::ALL.into_iter().enumerate() {
+            assert_eq!(cond as usize, i);
+        }
+    }
+
+    #[test]
+    fn inverse() {
+        use Cond::*;
+        // Every condition has a complement
+        let pairs = [
+            (Al, Nv),
+            (Eq, Ne),
+            (Lt, Ge),
+            (Le, Gt),
+            (Cc, Cs),
+            (Vc, Vs),
+            (Pl, Mi),
+        ];
+        for sr in 0x0000..=0x000f {
+            for (cond, inv) in pairs {
+                assert_ne!(cond.eval(sr), inv.eval(sr), "{:?} with sr={:#x}", cond, sr);
+            }
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use std::error::Error;
+use std::fmt::{self, Display};
+
+use crate::{legacy, opcode, uarch, Cond, Encoding};
+
+pub mod add;
+pub mod and;
+pub mod bra;
+pub mod cmp;
+pub mod hlt;
+pub mod iff;
+pub mod ldr;
+pub mod mov;
+pub mod mul;
+pub mod orr;
+pub mod rti;
+pub mod shf;
+pub mod str;
+pub mod sub;
+pub mod sys;
+pub mod xor;
+
+pub use self::add::Add;
+pub use self::and::And;
+pub use self::bra::Bra;
+pub use self::cmp::Cmp;
+pub use self::hlt::Hlt;
+pub use self::iff::Iff;
+pub use self::ldr::Ldr;
+pub use self::mov::Mov;
+pub use self::mul::Mul;
+pub use self::orr::Orr;
+pub use self::rti::Rti;
+pub use self::shf::Shf;
+pub use self::str::Str;
+pub use self::sub::Sub;
+pub use self::sys::Sys;
+pub use self::xor::Xor;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Op2 {
+    Reg(uarch),
+    Imm(uarch),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Instruction {
+    Add(Add),
+    And(And),
+    Bra(Bra),
+    Cmp(Cmp),
+    Hlt(Hlt),
+    Iff(Iff),
+    Ldr(Ldr),
+    Mov(Mov),
+    Mul(Mul),
+    Orr(Orr),
+    Rti(Rti),
+    Shf(Shf),
+    Str(Str),
+    Sub(Sub),
+    Sys(Sys),
+    Xor(Xor),
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Add(instr) => write!(f, "{}", instr),
+            Self::And(instr) => write!(f, "{}", instr),
+            Self::Bra(instr) => write!(f, "{}", instr),
+            Self::Cmp(instr) => write!(f, "{}", instr),
+            Self::Hlt(instr) => write!(f, "{}", instr),
+            Self::Iff(instr) => write!(f, "{}", instr),
+            Self::Ldr(instr) => write!(f, "{}", instr),
+            Self::Mov(instr) => write!(f, "{}", instr),
+            Self::Mul(instr) => write!(f, "{}", instr),
+            Self::Orr(instr) => write!(f, "{}", instr),
+            Self::Rti(instr) => write!(f, "{}", instr),
+            Self::Shf(instr) => write!(f, "{}", instr),
+            Self::Str(instr) => write!(f, "{}", instr),
+            Self::Sub(instr) => write!(f, "{}", instr),
+            Self::Sys(instr) => write!(f, "{}", instr),
+            Self::Xor(instr) => write!(f, "{}", instr),
+        }
+    }
+}
+
+impl Instruction {
+    /// Decodes an instruction word using the given encoding.
+    pub fn decode(word: uarch, enc: Encoding) -> Result<Self, DecodeError> {
+        match enc {
+            Encoding::V0 => legacy::decode(word),
+            Encoding::V1 => Self::try_from(word),
+        }
+    }
+
+    /// Encodes this instruction into a word using the given encoding.
+    pub fn encode(self, enc: Encoding) -> Result<uarch, EncodeError> {
+        match enc {
+            Encoding::V0 => legacy::encode(self).ok_or(EncodeError::Unsupported(self, enc)),
+            Encoding::V1 => match self {
+                Self::Bra(Bra { cond, .. }) if cond != Cond::Al => {
+                    Err(EncodeError::Unsupported(self, enc))
+                }
+                _ => Ok(self.into()),
+            },
+        }
+    }
+}
+
+impl TryFrom<uarch> for Instruction {
+    type Error = DecodeError;
+
+    fn try_from(word: uarch) -> Result<Self, Self::Error> {
+        Ok(match word {
+            word if opcode::ADD.matches(word) => Self::Add(Add::from(word)),
+            word if opcode::AND.matches(word) => Self::And(And::from(word)),
+            word if opcode::BRA.matches(word) => Self::Bra(Bra::from(word)),
+            word if opcode::CMP.matches(word) => Self::Cmp(Cmp::from(word)),
+            word if opcode::HLT.matches(word) => Self::Hlt(Hlt::from(word)),
+            word if opcode::IFF.matches(word) => Self::Iff(Iff::try_from(word)?),
+            word if opcode::LDR.matches(word) => Self::Ldr(Ldr::from(word)),
+            word if opcode::MOV.matches(word) => Self::Mov(Mov::try_from(word)?),
+            word if opcode::MUL.matches(word) => Self::Mul(Mul::from(word)),
+            word if opcode::ORR.matches(word) => Self::Orr(Orr::from(word)),
+            word if opcode::RTI.matches(word) => Self::Rti(Rti::from(word)),
+            word if opcode::SHF.matches(word) => Self::Shf(Shf::try_from(word)?),
+            word if opcode::STR.matches(word) => Self::Str(Str::from(word)),
+            word if opcode::SUB.matches(word) => Self::Sub(Sub::from(word)),
+            word if opcode::SYS.matches(word) => Self::Sys(Sys::from(word)),
+            word if opcode::XOR.matches(word) => Self::Xor(Xor::from(word)),
+            _ => return Err(DecodeError::Undefined(word)),
+        })
+    }
+}
+
+impl From<Instruction> for uarch {
+    fn from(instr: Instruction) -> Self {
+        match instr {
+            Instruction::Add(instr) => instr.into(),
+            Instruction::And(instr) => instr.into(),
+            Instruction::Bra(instr) => instr.into(),
+            Instruction::Cmp(instr) => instr.into(),
+            Instruction::Hlt(instr) => instr.into(),
+            Instruction::Iff(instr) => instr.into(),
+            Instruction::Ldr(instr) => instr.into(),
+            Instruction::Mov(instr) => instr.into(),
+            Instruction::Mul(instr) => instr.into(),
+            Instruction::Orr(instr) => instr.into(),
+            Instruction::Rti(instr) => instr.into(),
+            Instruction::Shf(instr) => instr.into(),
+            Instruction::Str(instr) => instr.into(),
+            Instruction::Sub(instr) => instr.into(),
+            Instruction::Sys(instr) => instr.into(),
+            Instruction::Xor(instr) => instr.into(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum EncodeError {
+    Unsupported(Instruction, Encoding),
+}
+
+impl Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Unsupported(instr, enc) => {
+                    format!("Cannot encode `{}` using encoding {}", instr, enc)
+                }
+            }
+        )
+    }
+}
+
+impl Error for EncodeError {}
+
+#[derive(Debug)]
+pub enum DecodeError {
+    Undefined(uarch),
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Undefined(word) => format!("Undefined instruction {:#06x}", word),
+            }
+        )
+    }
+}
+
+impl Error for DecodeError {}
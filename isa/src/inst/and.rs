@@ -0,0 +1,65 @@
+use std::fmt::{self, Display};
+
+use super::Op2;
+use crate::{opcode, reg, uarch, util};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct And {
+    pub op1: uarch,
+    pub op2: Op2,
+}
+
+impl Display for And {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let label = "and";
+        let op1 = reg::name(self.op1).to_string();
+        let op2 = match self.op2 {
+            Op2::Reg(op2) => reg::name(op2).to_string(),
+            Op2::Imm(imm) => format!("{:#06x}", imm),
+        };
+        write!(f, "{} {}, {}", label, op1, op2)
+    }
+}
+
+impl From<uarch> for And {
+    fn from(word: uarch) -> Self {
+        assert!(opcode::AND.matches(word));
+        Self {
+            op1: (word & 0x0f00) >> 8,
+            op2: match (word & 0x0080) == 0 {
+                true => Op2::Reg(word & 0x000f),
+                false => Op2::Imm(util::sign_extend::<7, { uarch::BITS }>(word & 0x007f)),
+            },
+        }
+    }
+}
+
+impl From<And> for uarch {
+    fn from(instr: And) -> Self {
+        let mut word: uarch = 0;
+        word |= opcode::AND.bits();
+        word |= (instr.op1 << 8) & 0x0f00;
+        word |= match instr.op2 {
+            Op2::Reg(op2) => op2,
+            Op2::Imm(imm) => 0x0080 | imm,
+        } & 0x00ff;
+        word
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sweep() {
+        for mut word in 0xe000..=0xefff {
+            let instr = And::from(word);
+            if let Op2::Reg(_) = instr.op2 {
+                word &= 0xff8f;
+            }
+            let decoded: uarch = instr.into();
+            assert_eq!(decoded, word);
+        }
+    }
+}
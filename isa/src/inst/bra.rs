@@ -0,0 +1,75 @@
+use std::fmt::{self, Display};
+
+use super::Op2;
+use crate::{opcode, reg, uarch, util, Cond, WORDSIZE};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Bra {
+    pub op2: Op2,
+    pub link: bool,
+    pub cond: Cond,
+}
+
+impl Display for Bra {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let label = match (self.cond, self.link) {
+            (Cond::Al, false) => "goto".to_string(),
+            (Cond::Al, true) => "call".to_string(),
+            (cond, link) => format!("b{}{:?}", if link { "l" } else { "" }, cond).to_lowercase(),
+        };
+        let op2 = match self.op2 {
+            Op2::Reg(op2) => reg::name(op2).to_string(),
+            Op2::Imm(imm) => format!("{:+#07x}", imm),
+        };
+        write!(f, "{} {}", label, op2)
+    }
+}
+
+impl From<uarch> for Bra {
+    fn from(word: uarch) -> Self {
+        assert!(opcode::BRA.matches(word));
+        Self {
+            op2: match (word & 0x0080) == 0 {
+                true => Op2::Reg(word & 0x000f),
+                false => Op2::Imm(util::sign_extend::<8, { uarch::BITS }>(
+                    (WORDSIZE as uarch) * (word & 0x007f),
+                )),
+            },
+            link: (word & 0x0400) != 0,
+            cond: Cond::Al,
+        }
+    }
+}
+
+impl From<Bra> for uarch {
+    fn from(instr: Bra) -> Self {
+        // Only unconditional branches can be encoded
+        assert_eq!(instr.cond, Cond::Al);
+        let mut word: uarch = 0;
+        word |= opcode::BRA.bits();
+        word |= ((instr.link as uarch) << 10) & 0x0400;
+        word |= match instr.op2 {
+            Op2::Reg(op2) => op2,
+            Op2::Imm(imm) => 0x0080 | (imm / (WORDSIZE as uarch)),
+        } & 0x00ff;
+        word
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sweep() {
+        for mut word in 0x0000..=0x07ff {
+            let instr = Bra::from(word);
+            word &= 0xfcff;
+            if let Op2::Reg(_) = instr.op2 {
+                word &= 0xff8f;
+            }
+            let decoded: uarch = instr.into();
+            assert_eq!(decoded, word);
+        }
+    }
+}
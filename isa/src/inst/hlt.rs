@@ -0,0 +1,43 @@
+use std::fmt::{self, Display};
+
+use crate::{opcode, uarch};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Hlt;
+
+impl Display for Hlt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let label = "hlt";
+        write!(f, "{}", label)
+    }
+}
+
+impl From<uarch> for Hlt {
+    fn from(word: uarch) -> Self {
+        assert!(opcode::HLT.matches(word));
+        Self
+    }
+}
+
+impl From<Hlt> for uarch {
+    fn from(_: Hlt) -> Self {
+        let mut word: uarch = 0;
+        word |= opcode::HLT.bits();
+        word
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sweep() {
+        for mut word in 0x0c00..=0x0dff {
+            let instr = Hlt::from(word);
+            word &= 0xfe00;
+            let decoded: uarch = instr.into();
+            assert_eq!(decoded, word);
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use std::fmt::{self, Display};
+
+use super::DecodeError;
+use crate::{opcode, uarch, Cond};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Iff {
+    pub cond: Cond,
+}
+
+impl Display for Iff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let label = format!("if{:?}", self.cond).to_lowercase();
+        write!(f, "{}", label)
+    }
+}
+
+impl TryFrom<uarch> for Iff {
+    type Error = DecodeError;
+
+    fn try_from(word: uarch) -> Result<Self, Self::Error> {
+        assert!(opcode::IFF.matches(word));
+        Ok(Self {
+            cond: *Cond::ALL
+                .get((word & 0x000f) as usize)
+                .ok_or(DecodeError::Undefined(word))?,
+        })
+    }
+}
+
+impl From<Iff> for uarch {
+    fn from(instr: Iff) -> Self {
+        let mut word: uarch = 0;
+        word |= opcode::IFF.bits();
+        word |= (instr.cond as uarch) & 0x000f;
+        word
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sweep() {
+        for mut word in 0x0e00..=0x0fff {
+            if (word & 0x000e) == 0x000e {
+                assert!(Iff::try_from(word).is_err());
+                continue;
+            }
+            let instr = Iff::try_from(word).unwrap();
+            word &= 0xfe0f;
+            let decoded: uarch = instr.into();
+            assert_eq!(decoded, word);
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use std::fmt::{self, Display};
+
+use super::Op2;
+use crate::{opcode, reg, uarch, util, WORDSIZE};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Mode {
+    Ldr = 0b0,
+    Pop = 0b1,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Ldr {
+    pub op1: uarch,
+    pub op2: Op2,
+    pub mode: Mode,
+}
+
+impl Display for Ldr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let label = format!("{:?}", self.mode).to_lowercase();
+        match self.mode {
+            Mode::Ldr => {
+                let op1 = reg::name(self.op1).to_string();
+                let op2 = match self.op2 {
+                    Op2::Reg(op2) => reg::name(op2).to_string(),
+                    Op2::Imm(imm) => format!("{:+#07x}", imm),
+                };
+                write!(f, "{} {}, *{}", label, op1, op2)
+            }
+            Mode::Pop => {
+                let op1 = reg::name(self.op1).to_string();
+                write!(f, "{} {}", label, op1)
+            }
+        }
+    }
+}
+
+impl From<uarch> for Ldr {
+    fn from(word: uarch) -> Self {
+        assert!(opcode::LDR.matches(word));
+        Self {
+            op1: (word & 0x0f00) >> 8,
+            op2: match (word & 0x0080) == 0 {
+                true => Op2::Reg(word & 0x000f),
+                false => Op2::Imm(util::sign_extend::<8, { uarch::BITS }>(
+                    (WORDSIZE as uarch) * (word & 0x007f),
+                )),
+            },
+            mode: match ((word ^ 0x0040) & 0x00c0) == 0 {
+                false => Mode::Ldr,
+                true => Mode::Pop,
+            },
+        }
+    }
+}
+
+impl From<Ldr> for uarch {
+    fn from(instr: Ldr) -> Self {
+        let mut word: uarch = 0;
+        word |= opcode::LDR.bits();
+        word |= (instr.op1 << 8) & 0x0f00;
+        word |= match instr.op2 {
+            Op2::Reg(op2) => match instr.mode {
+                Mode::Ldr => op2,
+                Mode::Pop => 0x0040,
+            },
+            Op2::Imm(imm) => 0x0080 | (imm / (WORDSIZE as uarch)),
+        } & 0x00ff;
+        word
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sweep() {
+        for mut word in 0x3000..=0x3fff {
+            let instr = Ldr::from(word);
+            if let Op2::Reg(_) = instr.op2 {
+                word &= 0xffcf;
+            }
+            if let Mode::Pop = instr.mode {
+                word &= 0xffc0;
+            }
+            let decoded: uarch = instr.into();
+            assert_eq!(decoded, word);
+        }
+    }
+}
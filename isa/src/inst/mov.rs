@@ -0,0 +1,88 @@
+use std::fmt::{self, Display};
+
+use super::{DecodeError, Op2};
+use crate::{opcode, reg, uarch, util};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Mode {
+    Mov = 0b00,
+    Neg = 0b01,
+    Not = 0b10,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Mov {
+    pub op1: uarch,
+    pub op2: Op2,
+    pub mode: Mode,
+}
+
+impl Display for Mov {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let label = format!("{:?}", self.mode).to_lowercase();
+        let op1 = reg::name(self.op1).to_string();
+        let op2 = match self.op2 {
+            Op2::Reg(op2) => reg::name(op2).to_string(),
+            Op2::Imm(imm) => format!("{:#06x}", imm),
+        };
+        write!(f, "{} {}, {}", label, op1, op2)
+    }
+}
+
+impl TryFrom<uarch> for Mov {
+    type Error = DecodeError;
+
+    fn try_from(word: uarch) -> Result<Self, Self::Error> {
+        assert!(opcode::MOV.matches(word));
+        Ok(Self {
+            op1: (word & 0x0f00) >> 8,
+            op2: match (word & 0x0080) == 0 {
+                true => Op2::Reg(word & 0x000f),
+                false => Op2::Imm(util::sign_extend::<7, { uarch::BITS }>(word & 0x007f)),
+            },
+            mode: match (word & 0x0080) != 0 {
+                true => Mode::Mov,
+                false => match (word & 0x0030) >> 4 {
+                    0b00 => Mode::Mov,
+                    0b01 => Mode::Neg,
+                    0b10 => Mode::Not,
+                    _ => return Err(DecodeError::Undefined(word)),
+                },
+            },
+        })
+    }
+}
+
+impl From<Mov> for uarch {
+    fn from(instr: Mov) -> Self {
+        let mut word: uarch = 0;
+        word |= opcode::MOV.bits();
+        word |= (instr.op1 << 8) & 0x0f00;
+        word |= match instr.op2 {
+            Op2::Reg(op2) => ((instr.mode as uarch) << 4) | op2,
+            Op2::Imm(imm) => 0x0080 | imm,
+        } & 0x00ff;
+        word
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sweep() {
+        for mut word in 0x7000..=0x7fff {
+            if (word & 0x00b0) >> 4 == 0b0011 {
+                assert!(Mov::try_from(word).is_err());
+                continue;
+            }
+            let instr = Mov::try_from(word).unwrap();
+            if let Op2::Reg(_) = instr.op2 {
+                word &= 0xffbf;
+            }
+            let decoded: uarch = instr.into();
+            assert_eq!(decoded, word);
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use std::fmt::{self, Display};
+
+use crate::{opcode, uarch};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Rti;
+
+impl Display for Rti {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let label = "rti";
+        write!(f, "{}", label)
+    }
+}
+
+impl From<uarch> for Rti {
+    fn from(word: uarch) -> Self {
+        assert!(opcode::RTI.matches(word));
+        Self
+    }
+}
+
+impl From<Rti> for uarch {
+    fn from(_: Rti) -> Self {
+        let mut word: uarch = 0;
+        word |= opcode::RTI.bits();
+        word
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sweep() {
+        for mut word in 0x0a00..=0x0bff {
+            let instr = Rti::from(word);
+            word &= 0xfe00;
+            let decoded: uarch = instr.into();
+            assert_eq!(decoded, word);
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use std::fmt::{self, Display};
+
+use super::{DecodeError, Op2};
+use crate::{opcode, reg, uarch};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Mode {
+    Lsr = 0b000,
+    Asr = 0b001,
+    Ror = 0b010,
+    Lsl = 0b100,
+    Asl = 0b101,
+    Rol = 0b110,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Shf {
+    pub op1: uarch,
+    pub op2: Op2,
+    pub mode: Mode,
+}
+
+impl Display for Shf {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let label = format!("{:?}", self.mode).to_lowercase();
+        let op1 = reg::name(self.op1).to_string();
+        let op2 = match self.op2 {
+            Op2::Reg(op2) => reg::name(op2).to_string(),
+            Op2::Imm(imm) => format!("{:#06x}", imm),
+        };
+        write!(f, "{} {}, {}", label, op1, op2)
+    }
+}
+
+impl TryFrom<uarch> for Shf {
+    type Error = DecodeError;
+
+    fn try_from(word: uarch) -> Result<Self, Self::Error> {
+        assert!(opcode::SHF.matches(word));
+        Ok(Self {
+            op1: (word & 0x0f00) >> 8,
+            op2: match (word & 0x0080) == 0 {
+                true => Op2::Reg(word & 0x000f),
+                false => Op2::Imm(word & 0x000f),
+            },
+            mode: match (word & 0x0070) >> 4 {
+                0b000 => Mode::Lsr,
+                0b001 => Mode::Asr,
+                0b010 => Mode::Ror,
+                0b100 => Mode::Lsl,
+                0b101 => Mode::Asl,
+                0b110 => Mode::Rol,
+                _ => return Err(DecodeError::Undefined(word)),
+            },
+        })
+    }
+}
+
+impl From<Shf> for uarch {
+    fn from(instr: Shf) -> Self {
+        let mut word: uarch = 0;
+        word |= opcode::SHF.bits();
+        word |= (instr.op1 << 8) & 0x0f00;
+        word |= ((instr.mode as uarch) << 4) & 0x0070;
+        word |= match instr.op2 {
+            Op2::Reg(op2) => op2,
+            Op2::Imm(imm) => 0x0080 | imm,
+        } & 0x00ff;
+        word
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sweep() {
+        for word in 0xf000..=0xffff {
+            if (word & 0x0030) >> 4 == 0b11 {
+                assert!(Shf::try_from(word).is_err());
+                continue;
+            }
+            let instr = Shf::try_from(word).unwrap();
+            let decoded: uarch = instr.into();
+            assert_eq!(decoded, word);
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use std::fmt::{self, Display};
+
+use super::Op2;
+use crate::{opcode, reg, uarch};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Mode {
+    Sub = 0b0,
+    Rsb = 0b1,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Sub {
+    pub op1: uarch,
+    pub op2: Op2,
+    pub mode: Mode,
+}
+
+impl Display for Sub {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let label = format!("{:?}", self.mode).to_lowercase();
+        let op1 = reg::name(self.op1).to_string();
+        let op2 = match self.op2 {
+            Op2::Reg(op2) => reg::name(op2).to_string(),
+            Op2::Imm(imm) => format!("{:#06x}", imm),
+        };
+        write!(f, "{} {}, {}", label, op1, op2)
+    }
+}
+
+impl From<uarch> for Sub {
+    fn from(word: uarch) -> Self {
+        assert!(opcode::SUB.matches(word));
+        Self {
+            op1: (word & 0x0f00) >> 8,
+            op2: match (word & 0x0080) == 0 {
+                true => Op2::Reg(word & 0x000f),
+                false => Op2::Imm(word & 0x007f),
+            },
+            mode: match (word & 0x1000) >> 12 {
+                0b0 => Mode::Sub,
+                0b1 => Mode::Rsb,
+                _ => panic!(),
+            },
+        }
+    }
+}
+
+impl From<Sub> for uarch {
+    fn from(instr: Sub) -> Self {
+        let mut word: uarch = 0;
+        word |= opcode::SUB.bits();
+        word |= ((instr.mode as uarch) << 12) & 0x1000;
+        word |= (instr.op1 << 8) & 0x0f00;
+        word |= match instr.op2 {
+            Op2::Reg(op2) => op2,
+            Op2::Imm(imm) => 0x0080 | imm,
+        } & 0x00ff;
+        word
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sweep() {
+        for mut word in 0x4000..=0x5fff {
+            let instr = Sub::from(word);
+            if let Op2::Reg(_) = instr.op2 {
+                word &= 0xff8f;
+            }
+            let decoded: uarch = instr.into();
+            assert_eq!(decoded, word);
+        }
+    }
+}
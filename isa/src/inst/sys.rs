@@ -0,0 +1,43 @@
+use std::fmt::{self, Display};
+
+use crate::{opcode, uarch};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Sys;
+
+impl Display for Sys {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let label = "sys";
+        write!(f, "{}", label)
+    }
+}
+
+impl From<uarch> for Sys {
+    fn from(word: uarch) -> Self {
+        assert!(opcode::SYS.matches(word));
+        Self
+    }
+}
+
+impl From<Sys> for uarch {
+    fn from(_: Sys) -> Self {
+        let mut word: uarch = 0;
+        word |= opcode::SYS.bits();
+        word
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sweep() {
+        for mut word in 0x0800..=0x09ff {
+            let instr = Sys::from(word);
+            word &= 0xfe00;
+            let decoded: uarch = instr.into();
+            assert_eq!(decoded, word);
+        }
+    }
+}
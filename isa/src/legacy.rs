@@ -0,0 +1,155 @@
+//! Legacy (V0) instruction encoding.
+//!
+//! Before adopting the spec's Huffman opcodes, instructions were encoded with
+//! a fixed-width 4-bit opcode in their most significant nibble. Apart from
+//! branches, which used to carry a condition, the operand layouts are shared
+//! with the current encoding, so words are translated by swapping opcodes.
+
+use crate::inst::bra::Bra;
+use crate::inst::{DecodeError, Instruction};
+use crate::opcode::{self, Opcode};
+use crate::{uarch, Cond};
+
+const ADD: Opcode = Opcode::new(0b1100, 4);
+const AND: Opcode = Opcode::new(0b0110, 4);
+const BRA: Opcode = Opcode::new(0b1111, 4);
+const CMP: Opcode = Opcode::new(0b00, 2);
+const LDR: Opcode = Opcode::new(0b1011, 4);
+const MOV: Opcode = Opcode::new(0b1010, 4);
+const MUL: Opcode = Opcode::new(0b0111, 4);
+const ORR: Opcode = Opcode::new(0b0100, 4);
+const SHF: Opcode = Opcode::new(0b1110, 4);
+const STR: Opcode = Opcode::new(0b1101, 4);
+const SUB: Opcode = Opcode::new(0b100, 3);
+const XOR: Opcode = Opcode::new(0b0101, 4);
+
+/// Legacy branch conditions, ordered by their encoding.
+const CONDS: [Cond; 7] = [
+    Cond::Al,
+    Cond::Eq,
+    Cond::Ne,
+    Cond::Lt,
+    Cond::Le,
+    Cond::Ge,
+    Cond::Gt,
+];
+
+/// Legacy opcodes paired with their current equivalents.
+const TABLE: [(Opcode, Opcode); 11] = [
+    (ADD, opcode::ADD),
+    (AND, opcode::AND),
+    (CMP, opcode::CMP),
+    (LDR, opcode::LDR),
+    (MOV, opcode::MOV),
+    (MUL, opcode::MUL),
+    (ORR, opcode::ORR),
+    (SHF, opcode::SHF),
+    (STR, opcode::STR),
+    (SUB, opcode::SUB),
+    (XOR, opcode::XOR),
+];
+
+pub fn decode(word: uarch) -> Result<Instruction, DecodeError> {
+    // Branches are laid out differently
+    if BRA.matches(word) {
+        return decode_bra(word).map(Instruction::Bra);
+    }
+    // Translate the opcode to the current encoding
+    let (old, new) = TABLE
+        .iter()
+        .find(|(old, _)| old.matches(word))
+        .expect("legacy opcodes cover every word");
+    Instruction::try_from(translate(word, old, new))
+}
+
+/// Encodes an instruction, if it existed in the legacy encoding.
+pub fn encode(instr: Instruction) -> Option<uarch> {
+    // Branches are laid out differently
+    if let Instruction::Bra(instr) = instr {
+        return encode_bra(instr);
+    }
+    // Translate the opcode from the current encoding
+    let word = uarch::from(instr);
+    let (old, new) = TABLE.iter().find(|(_, new)| new.matches(word))?;
+    Some(translate(word, new, old))
+}
+
+fn translate(word: uarch, from: &Opcode, to: &Opcode) -> uarch {
+    to.bits() | (word & !from.mask())
+}
+
+fn decode_bra(word: uarch) -> Result<Bra, DecodeError> {
+    // Decode the operand using the current encoding
+    let Bra { op2, .. } = Bra::from(opcode::BRA.bits() | (word & 0x00ff));
+    Ok(Bra {
+        op2,
+        link: (word & 0x0800) != 0,
+        cond: *CONDS
+            .get(((word & 0x0700) >> 8) as usize)
+            .ok_or(DecodeError::Undefined(word))?,
+    })
+}
+
+fn encode_bra(instr: Bra) -> Option<uarch> {
+    // Only some conditions can be encoded
+    let cond = CONDS.iter().position(|&cond| cond == instr.cond)? as uarch;
+    // Encode the operand using the current encoding
+    let op2 = uarch::from(Bra {
+        cond: Cond::Al,
+        ..instr
+    }) & 0x00ff;
+    let mut word: uarch = 0;
+    word |= BRA.bits();
+    word |= ((instr.link as uarch) << 11) & 0x0800;
+    word |= (cond << 8) & 0x0700;
+    word |= op2;
+    Some(word)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Op2;
+
+    #[test]
+    fn complete() {
+        for word in 0x0000..=0xffff {
+            let count = TABLE.iter().filter(|(old, _)| old.matches(word)).count()
+                + BRA.matches(word) as usize;
+            assert_eq!(count, 1, "{:#06x} matched {} opcodes", word, count);
+        }
+    }
+
+    #[test]
+    fn sweep() {
+        for word in 0x0000..=0xffff {
+            // Ensure words with undefined modes are rejected
+            let undefined = match word >> 12 {
+                0b1010 => (word & 0x00b0) == 0x0030,
+                0b1110 => (word & 0x0030) == 0x0030,
+                0b1111 => (word & 0x0700) == 0x0700,
+                _ => false,
+            };
+            if undefined {
+                assert!(decode(word).is_err());
+                continue;
+            }
+            let encoded = encode(decode(word).unwrap()).unwrap();
+            assert_eq!(encode(decode(encoded).unwrap()), Some(encoded));
+        }
+    }
+
+    #[test]
+    fn bra() {
+        let instr = decode(0xfb85).unwrap();
+        assert_eq!(
+            instr,
+            Instruction::Bra(Bra {
+                op2: Op2::Imm(0x000a),
+                link: true,
+                cond: Cond::Lt,
+            })
+        );
+        assert_eq!(encode(instr), Some(0xfb85));
+    }
+}
@@ -0,0 +1,75 @@
+//! # Instruction Set Architecture
+//!
+//! `isa` defines LANv1, the instruction set of the KAP-16 microprocessor.
+//!
+//! It owns the instruction types along with their machine encodings and
+//! disassembly, and is shared between the assembler and the emulator.
+
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::mem;
+use std::str::FromStr;
+
+pub mod cond;
+pub mod inst;
+pub mod legacy;
+pub mod opcode;
+pub mod reg;
+pub mod util;
+
+pub use crate::cond::Cond;
+pub use crate::inst::{Instruction, Op2};
+
+#[allow(non_camel_case_types)]
+pub type iarch = i16;
+#[allow(non_camel_case_types)]
+pub type uarch = u16;
+
+pub const WORDSIZE: usize = mem::size_of::<uarch>();
+
+/// Revision of the machine encoding used for instruction words.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Encoding {
+    /// Original encoding, using fixed-width 4-bit opcodes.
+    V0,
+    /// Current encoding, using the spec's variable-width Huffman opcodes.
+    #[default]
+    V1,
+}
+
+impl Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", format!("{:?}", self).to_lowercase())
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = EncodingError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match &*s.to_lowercase() {
+            "v0" => Ok(Self::V0),
+            "v1" => Ok(Self::V1),
+            _ => Err(EncodingError::Unknown(s.to_string())),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum EncodingError {
+    Unknown(String),
+}
+
+impl Display for EncodingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Unknown(s) => format!("Unknown encoding `{}`; expected `v0` or `v1`", s),
+            }
+        )
+    }
+}
+
+impl Error for EncodingError {}
@@ -0,0 +1,104 @@
+//! Opcode map for LANv1 core instructions.
+//!
+//! Opcodes are variable-width [Huffman codes][huffman-codings], generated
+//! from the instruction weights in `docs/huffman/data/inst.csv`. The resulting
+//! table is kept in `docs/huffman/data/opcodes.csv`.
+//!
+//! `RTI` was later carved out of the space reserved by `SYS`, by splitting its
+//! codeword in two. This leaves every other opcode unchanged.
+//!
+//! [huffman-codings]: https://en.wikipedia.org/wiki/Huffman_coding
+
+use crate::uarch;
+
+pub const ADD: Opcode = Opcode::new(0b1100, 4);
+pub const AND: Opcode = Opcode::new(0b1110, 4);
+pub const BRA: Opcode = Opcode::new(0b00000, 5);
+pub const CMP: Opcode = Opcode::new(0b10, 2);
+pub const HLT: Opcode = Opcode::new(0b0000110, 7);
+pub const IFF: Opcode = Opcode::new(0b0000111, 7);
+pub const LDR: Opcode = Opcode::new(0b0011, 4);
+pub const MOV: Opcode = Opcode::new(0b0111, 4);
+pub const MUL: Opcode = Opcode::new(0b0110, 4);
+pub const ORR: Opcode = Opcode::new(0b1101, 4);
+pub const SHF: Opcode = Opcode::new(0b1111, 4);
+pub const STR: Opcode = Opcode::new(0b0010, 4);
+pub const RTI: Opcode = Opcode::new(0b0000101, 7);
+pub const SUB: Opcode = Opcode::new(0b010, 3);
+pub const SYS: Opcode = Opcode::new(0b0000100, 7);
+pub const XOR: Opcode = Opcode::new(0b0001, 4);
+
+/// A variable-width opcode occupying an instruction's most significant bits.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Opcode {
+    code: uarch,
+    width: u32,
+}
+
+impl Opcode {
+    pub const fn new(code: uarch, width: u32) -> Self {
+        Self { code, width }
+    }
+
+    /// Opcode bits, shifted into position within an instruction word.
+    pub const fn bits(&self) -> uarch {
+        self.code << (uarch::BITS - self.width)
+    }
+
+    /// Mask covering the opcode bits of an instruction word.
+    pub const fn mask(&self) -> uarch {
+        !(uarch::MAX >> self.width)
+    }
+
+    /// Checks if an instruction word begins with this opcode.
+    pub const fn matches(&self, word: uarch) -> bool {
+        (word & self.mask()) == self.bits()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn table() {
+        let csv = include_str!("../../docs/huffman/data/opcodes.csv");
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some("symbol,weight,codeword"));
+        for line in lines {
+            let row: Vec<_> = line.split(',').collect();
+            let opcode = match row[0] {
+                "ADD" => ADD,
+                "AND" => AND,
+                "BRA" => BRA,
+                "CMP" => CMP,
+                "HLT" => HLT,
+                "IFF" => IFF,
+                "LDR" => LDR,
+                "MOV" => MOV,
+                "MUL" => MUL,
+                "ORR" => ORR,
+                "RTI" => RTI,
+                "SHF" => SHF,
+                "STR" => STR,
+                "SUB" => SUB,
+                "SYS" => SYS,
+                "XOR" => XOR,
+                symbol => panic!("Unknown symbol: {}", symbol),
+            };
+            let code = uarch::from_str_radix(row[2], 2).unwrap();
+            assert_eq!(opcode, Opcode::new(code, row[2].len() as u32));
+        }
+    }
+
+    #[test]
+    fn prefix() {
+        let table = [
+            ADD, AND, BRA, CMP, HLT, IFF, LDR, MOV, MUL, ORR, RTI, SHF, STR, SUB, SYS, XOR,
+        ];
+        for word in 0x0000..=0xffff {
+            let count = table.iter().filter(|op| op.matches(word)).count();
+            assert_eq!(count, 1, "{:#06x} matched {} opcodes", word, count);
+        }
+    }
+}
@@ -0,0 +1,39 @@
+//! Register names.
+//!
+//! Registers are referred to by their conventional aliases where one exists,
+//! as outlined by the spec's register table.
+
+use crate::uarch;
+
+/// Number of general purpose registers.
+pub const COUNT: usize = 16;
+
+/// Conventional name of each register.
+pub const NAMES: [&str; COUNT] = [
+    "a0", "a1", "a2", "a3", "g0", "g1", "g2", "g3", "g4", "g5", "g6", "g7", "g8", "sp", "lr", "pc",
+];
+
+/// Returns the conventional name of a register.
+pub fn name(reg: uarch) -> &'static str {
+    NAMES[reg as usize % COUNT]
+}
+
+/// Looks up a register by its conventional name.
+pub fn lookup(name: &str) -> Option<uarch> {
+    NAMES
+        .iter()
+        .position(|&alias| alias == name)
+        .map(|reg| reg as uarch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        for reg in 0..COUNT as uarch {
+            assert_eq!(lookup(name(reg)), Some(reg));
+        }
+    }
+}
@@ -1,4 +1,4 @@
-use super::{iarch, uarch};
+use crate::{iarch, uarch};
 
 pub fn sign_extend<const F: u32, const T: u32>(x: uarch) -> uarch {
     assert!(T > F);
@@ -14,12 +14,13 @@
 ; @ret0 qot: quotient,  equal to `num / den`
 ; @ret1 rem: remainder, equal to `num % den`
 ;
+.global _divide
 .func
 _divide:
     mov r2, 0d0         ; let rem: r2 = 0
 .repeat 16
 .begin ; long division
-    shr a0, 0d1
+    lsr a0, 0d1
     ifcs
     orr r2, 0b1
     cmp r2, a1
@@ -28,6 +29,7 @@ _divide:
     sub r2, a1
     orr a0, 0b1
 next:
+.end
 .end
     mov a1, a2          ; let qot: ret0 = cur
     mov pc, lr          ; return